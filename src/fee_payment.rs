@@ -0,0 +1,168 @@
+//! Signed extension paying the fees of asset transfers in the transferred asset.
+//!
+//! Accounts holding only a `pallet_assets` token cannot pay the extrinsic fee of transferring it
+//! in Currency. `ChargeFeeInAsset` replaces `ChargeTransactionPayment` in the runtime's
+//! `SignedExtra`, charging the fee of the token dealer transfers of assets with a fee rate in the
+//! asset, and the fee of any other extrinsic as `ChargeTransactionPayment` does.
+
+use crate::{AssetIdOf, BalanceOf, Call, Counterparty, Module, Trait};
+use codec::{Decode, Encode};
+use frame_support::{
+    traits::{Currency, IsSubType},
+    weights::{DispatchInfo, PostDispatchInfo},
+};
+use pallet_transaction_payment::ChargeTransactionPayment;
+use sp_runtime::{
+    traits::{
+        DispatchInfoOf, Dispatchable, PostDispatchInfoOf, SaturatedConversion, SignedExtension,
+    },
+    transaction_validity::{
+        InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+        ValidTransaction,
+    },
+    DispatchResult, FixedPointOperand,
+};
+
+/// type, balances representation of the Currency paying transaction fees
+pub type PaymentBalanceOf<T> = <<T as pallet_transaction_payment::Trait>::Currency as Currency<
+    <T as frame_system::Trait>::AccountId,
+>>::Balance;
+
+/// Pay the fee of an extrinsic, and the tip, in the transferred asset if the extrinsic is a
+/// transfer of an asset with a fee rate, see `Module::fee_rate`.
+///
+/// Fees paid in the asset are moved to the account of the `AssetFeeHandler` and are not
+/// refunded after dispatch.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeFeeInAsset<T: Trait + pallet_transaction_payment::Trait + Send + Sync>(
+    #[codec(compact)] PaymentBalanceOf<T>,
+);
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> ChargeFeeInAsset<T>
+where
+    PaymentBalanceOf<T>: Send + Sync + FixedPointOperand,
+    <T as frame_system::Trait>::Call:
+        Dispatchable<Info = DispatchInfo, PostInfo = PostDispatchInfo> + IsSubType<Call<T>>,
+{
+    /// utility constructor. Used only in client/factory code.
+    pub fn from(tip: PaymentBalanceOf<T>) -> Self {
+        Self(tip)
+    }
+
+    /// The counterparty, asset, fee in the asset and fee in Currency of `call` if it transfers
+    /// an asset whose fees are paid in it.
+    fn asset_fee(
+        &self,
+        call: &<T as frame_system::Trait>::Call,
+        info: &DispatchInfoOf<<T as frame_system::Trait>::Call>,
+        len: usize,
+    ) -> Option<(
+        Counterparty,
+        Option<AssetIdOf<T>>,
+        BalanceOf<T>,
+        PaymentBalanceOf<T>,
+    )> {
+        let (counterparty, asset_id) = match call.is_sub_type()? {
+            Call::transfer_tokens_to_relay_chain(_, _, asset_id)
+            | Call::transfer_tokens_to_relay_chain_keep_alive(_, _, asset_id) => {
+                (Counterparty::Relay, asset_id)
+            }
+            Call::transfer_assets_to_parachain_chain(para_id, _, _, asset_id) => {
+                (Counterparty::Parachain((*para_id).into()), asset_id)
+            }
+            _ => return None,
+        };
+        let fee = pallet_transaction_payment::Module::<T>::compute_fee(len as u32, info, self.0);
+        let asset_fee =
+            Module::<T>::fee_in_asset(asset_id, fee.saturated_into::<u128>().saturated_into())?;
+        Some((counterparty, *asset_id, asset_fee, fee))
+    }
+}
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> sp_std::fmt::Debug
+    for ChargeFeeInAsset<T>
+{
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        write!(f, "ChargeFeeInAsset<{:?}>", self.0)
+    }
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> SignedExtension
+    for ChargeFeeInAsset<T>
+where
+    PaymentBalanceOf<T>: Send + Sync + From<u64> + FixedPointOperand,
+    <T as frame_system::Trait>::Call:
+        Dispatchable<Info = DispatchInfo, PostInfo = PostDispatchInfo> + IsSubType<Call<T>>,
+{
+    const IDENTIFIER: &'static str = "ChargeFeeInAsset";
+    type AccountId = T::AccountId;
+    type Call = <T as frame_system::Trait>::Call;
+    type AdditionalSigned = ();
+    /// `None` when the fee was paid in the asset.
+    type Pre = Option<<ChargeTransactionPayment<T> as SignedExtension>::Pre>;
+
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        who: &Self::AccountId,
+        call: &Self::Call,
+        info: &DispatchInfoOf<Self::Call>,
+        len: usize,
+    ) -> TransactionValidity {
+        match self.asset_fee(call, info, len) {
+            Some((_, asset_id, asset_fee, fee)) => {
+                if Module::<T>::balance_of(&asset_id, who) < asset_fee {
+                    return Err(InvalidTransaction::Payment.into());
+                }
+                // prioritised by the fee in Currency, as `ChargeTransactionPayment` does
+                Ok(ValidTransaction {
+                    priority: fee.saturated_into::<TransactionPriority>(),
+                    ..Default::default()
+                })
+            }
+            None => ChargeTransactionPayment::<T>::from(self.0).validate(who, call, info, len),
+        }
+    }
+
+    fn pre_dispatch(
+        self,
+        who: &Self::AccountId,
+        call: &Self::Call,
+        info: &DispatchInfoOf<Self::Call>,
+        len: usize,
+    ) -> Result<Self::Pre, TransactionValidityError> {
+        match self.asset_fee(call, info, len) {
+            Some((counterparty, asset_id, asset_fee, _)) => {
+                Module::<T>::charge_asset_fee(counterparty, &asset_id, who, asset_fee)
+                    .map_err(|_| InvalidTransaction::Payment)?;
+                Ok(None)
+            }
+            None => ChargeTransactionPayment::<T>::from(self.0)
+                .pre_dispatch(who, call, info, len)
+                .map(Some),
+        }
+    }
+
+    fn post_dispatch(
+        pre: Self::Pre,
+        info: &DispatchInfoOf<Self::Call>,
+        post_info: &PostDispatchInfoOf<Self::Call>,
+        len: usize,
+        result: &DispatchResult,
+    ) -> Result<(), TransactionValidityError> {
+        match pre {
+            Some(pre) => {
+                ChargeTransactionPayment::<T>::post_dispatch(pre, info, post_info, len, result)
+            }
+            None => Ok(()),
+        }
+    }
+}
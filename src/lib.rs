@@ -1,24 +1,48 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use codec::{Decode, Encode};
+use codec::{Decode, DecodeAll, Encode};
 use cumulus_primitives::{
     relay_chain::DownwardMessage,
     xcmp::{XCMPMessageHandler, XCMPMessageSender},
     DownwardMessageHandler, ParaId, UpwardMessageOrigin, UpwardMessageSender,
 };
 use frame_support::{
-    decl_event, decl_module,
+    decl_error, decl_event, decl_module, decl_storage,
     dispatch::DispatchResult,
-    traits::{Currency, ExistenceRequirement},
+    ensure,
+    storage::{IterableStorageDoubleMap, IterableStorageMap},
+    traits::{Currency, EnsureOrigin, ExistenceRequirement, Get, OnUnbalanced, WithdrawReason},
+    weights::Weight,
 };
 use frame_system::ensure_signed;
 use pallet_assets as assets;
 use polkadot_parachain::primitives::AccountIdConversion;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::{
+    traits::{Hash as HashT, One, SaturatedConversion, Saturating, Zero},
+    DispatchError, FixedPointNumber, FixedU128, Perbill, RuntimeDebug,
+};
+use sp_std::{
+    convert::{TryFrom, TryInto},
+    prelude::*,
+    vec,
+};
 
 // upward_message here is the same for cumulus rococo V1
 // included here in preparation for Cumulus V1
 pub mod upward_messages;
-pub use crate::upward_messages::BalancesMessage;
+pub use crate::upward_messages::{BalancesMessage, EncodedRelayCall, RelayCallIndices};
+
+// XCM v0 message format spoken with other parachains
+pub mod xcm;
+
+// runtime API to query the reconciliation of the counterparty accounts
+pub mod runtime_api;
+
+// signed extension paying the fees of asset transfers in the transferred asset
+pub mod fee_payment;
+use crate::xcm::{Junction, MultiAsset, MultiLocation, NetworkId, Order, VersionedXcm, Xcm};
 
 mod mock;
 mod tests;
@@ -30,9 +54,44 @@ pub type AssetIdOf<T> = <T as assets::Trait>::AssetId;
 pub type BalanceOf<T> =
     <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
 
-/// Unique identifier for the Relay Chain account
-#[derive(Clone, Copy, Decode, Default, Encode, Eq, Hash, PartialEq)]
-pub struct RelayAccount();
+/// type, imbalance of the Currency withdrawn to pay transfer fees
+pub type NegativeImbalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
+
+/// Unique identifier for the Relay Chain account, indexed to segregate funds per
+/// `AccountPurpose`. Index 0 is the account the Relay Chain transfers are settled with.
+#[derive(Clone, Copy, Decode, Default, Encode, Eq, Hash, PartialEq, RuntimeDebug)]
+pub struct RelayAccount(pub u16);
+
+/// Unique identifier for a sibling parachain's account, indexed to segregate funds per
+/// `AccountPurpose`. Index 0 is the account derived from the `ParaId` alone.
+#[derive(Clone, Copy, Decode, Encode, Eq, Hash, PartialEq, RuntimeDebug)]
+pub struct SiblingAccount(pub ParaId, pub u16);
+
+/// Purposes for which funds held for another chain are kept in separate sub-accounts.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum AccountPurpose {
+    /// Tokens transferred by users, index 0.
+    #[codec(index = "0")]
+    Transfers,
+    /// Fees charged on transfers, index 1.
+    #[codec(index = "1")]
+    Fees,
+    /// Funds of delayed transfers to the counterparty, index 2.
+    #[codec(index = "2")]
+    Escrow,
+}
+
+impl AccountPurpose {
+    /// Index of the sub-account holding funds for this purpose.
+    pub fn index(self) -> u16 {
+        match self {
+            AccountPurpose::Transfers => 0,
+            AccountPurpose::Fees => 1,
+            AccountPurpose::Escrow => 2,
+        }
+    }
+}
 
 /// structure to help with Relay Chain Account setup
 /// reference: polkadot/parachains/src/primitives.rs
@@ -53,10 +112,10 @@ impl<'a> codec::Input for TrailingZeroInput<'a> {
     }
 }
 
-/// Format is b"Relay" ++ 00...; zeroes to fill AccountId.
+/// Format is b"Relay" ++ encode(index) ++ 00...; zeroes to fill AccountId.
 impl<T: Encode + Decode> AccountIdConversion<T> for RelayAccount {
     fn into_account(&self) -> T {
-        (b"Relay")
+        (b"Relay", self)
             .using_encoded(|b| T::decode(&mut TrailingZeroInput(b)))
             .unwrap()
     }
@@ -77,11 +136,304 @@ impl<T: Encode + Decode> AccountIdConversion<T> for RelayAccount {
     }
 }
 
-/// Represent XCMP Message between parachains
+/// Format is b"para" ++ encode(parachain ID) ++ encode(index) ++ 00...; zeroes to fill
+/// AccountId, as for `ParaId` with index 0.
+impl<T: Encode + Decode> AccountIdConversion<T> for SiblingAccount {
+    fn into_account(&self) -> T {
+        (b"para", self)
+            .using_encoded(|b| T::decode(&mut TrailingZeroInput(b)))
+            .unwrap()
+    }
+
+    fn try_from_account(x: &T) -> Option<Self> {
+        x.using_encoded(|d| {
+            if &d[0..4] != b"para" {
+                return None;
+            }
+            let mut cursor = &d[4..];
+            let result = Decode::decode(&mut cursor).ok()?;
+            if cursor.iter().all(|x| *x == 0) {
+                Some(result)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// A chain this parachain transfers tokens with.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Counterparty {
+    /// The Relay Chain.
+    Relay,
+    /// A sibling parachain.
+    Parachain(ParaId),
+}
+
+/// Derivation of the accounts holding, on this parachain, the funds of other chains.
+pub trait SovereignAccountOf<AccountId> {
+    /// The account holding the funds for `purpose` of `counterparty`.
+    fn sovereign_account(counterparty: Counterparty, purpose: AccountPurpose) -> AccountId;
+}
+
+/// Derives `RelayAccount` and `SiblingAccount` accounts, which for `AccountPurpose::Transfers`
+/// match the `polkadot_parachain` derivation of `ParaId` accounts.
+pub struct IndexedSovereignAccounts;
+
+impl<AccountId: Encode + Decode> SovereignAccountOf<AccountId> for IndexedSovereignAccounts {
+    fn sovereign_account(counterparty: Counterparty, purpose: AccountPurpose) -> AccountId {
+        match counterparty {
+            Counterparty::Relay => RelayAccount(purpose.index()).into_account(),
+            Counterparty::Parachain(para_id) => {
+                SiblingAccount(para_id, purpose.index()).into_account()
+            }
+        }
+    }
+}
+
+/// Direction of the transfers with other chains.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Direction {
+    /// Transfers from this parachain.
+    Outbound,
+    /// Transfers to this parachain.
+    Inbound,
+}
+
+/// Listing of a parachain, for a direction, in the allowlist or the denylist.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Access {
+    /// Transfers with the parachain are allowed, even when the allowlist is required.
+    Allowed,
+    /// Transfers with the parachain are rejected.
+    Denied,
+}
+
+/// Transfers affected by a pause.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum PauseScope<AssetId> {
+    /// Transfers of all assets with all chains.
+    All,
+    /// Transfers with the counterparty.
+    Counterparty(Counterparty),
+    /// Transfers of the local asset, or Currency when `None`.
+    Asset(Option<AssetId>),
+}
+
+/// Compliance checks of transfers, e.g. against identity or sanctions lists kept by another
+/// pallet.
+pub trait TransferFilter<AccountId, AssetId, Balance> {
+    /// Whether `from` may transfer `amount` of the asset, or Currency when `asset_id` is `None`,
+    /// to `dest` on `counterparty`.
+    fn allow_outbound(
+        from: &AccountId,
+        counterparty: Counterparty,
+        dest: &AccountId,
+        asset_id: &Option<AssetId>,
+        amount: Balance,
+    ) -> bool;
+
+    /// Whether `amount` of the asset, or Currency when `asset_id` is `None`, received from
+    /// `counterparty` may be credited to `dest`.
+    fn allow_inbound(
+        counterparty: Counterparty,
+        dest: &AccountId,
+        asset_id: &Option<AssetId>,
+        amount: Balance,
+    ) -> bool;
+}
+
+impl<AccountId, AssetId, Balance> TransferFilter<AccountId, AssetId, Balance> for () {
+    fn allow_outbound(
+        _: &AccountId,
+        _: Counterparty,
+        _: &AccountId,
+        _: &Option<AssetId>,
+        _: Balance,
+    ) -> bool {
+        true
+    }
+
+    fn allow_inbound(_: Counterparty, _: &AccountId, _: &Option<AssetId>, _: Balance) -> bool {
+        true
+    }
+}
+
+/// Handling of inbound amounts too small to create the beneficiary's account.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum DustPolicy<AccountId> {
+    /// Keep them in `PendingCredits` until their total is enough to create the account.
+    Accumulate,
+    /// Transfer them, with any pending credits of the beneficiary, to the given account once it
+    /// holds the asset; keep them in `PendingCredits` until then.
+    RouteTo(AccountId),
+}
+
+/// The flows of an asset with a counterparty and the balance of its account on this parachain.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct Reconciliation<AssetId, Balance> {
+    /// The chain the asset was transferred with.
+    pub counterparty: Counterparty,
+    /// The local asset, or Currency when `None`.
+    pub asset_id: Option<AssetId>,
+    /// Total amount paid out from the account of the counterparty.
+    pub inflow: Balance,
+    /// Total amount moved into the account of the counterparty.
+    pub outflow: Balance,
+    /// Balance of the account of the counterparty before any flow, see `OpeningBalances`.
+    pub opening: Balance,
+    /// Balance of the account of the counterparty.
+    pub balance: Balance,
+}
+
+/// Fee charged in Currency to the sender of an outbound transfer, on top of the amount.
+#[derive(Clone, Copy, Decode, Default, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct TransferFee<Balance> {
+    /// Charged on every transfer.
+    pub flat: Balance,
+    /// Share of the transferred amount charged on top of `flat`.
+    pub rate: Perbill,
+}
+
+/// Source of the rates at which transfer fees can be paid in an asset, e.g. a price oracle.
+pub trait FeeRateOracle<AssetId> {
+    /// Units of the asset paid per unit of Currency, `None` if fees cannot be paid in it.
+    fn fee_rate(asset_id: &AssetId) -> Option<FixedU128>;
+}
+
+impl<AssetId> FeeRateOracle<AssetId> for () {
+    fn fee_rate(_: &AssetId) -> Option<FixedU128> {
+        None
+    }
+}
+
+/// Handler of the fees paid in assets, which `FeeHandler` cannot take as it handles Currency
+/// imbalances.
+pub trait AssetFeeHandler<AccountId, AssetId> {
+    /// The account receiving the fees in `asset_id` of transfers with `counterparty`, e.g. the
+    /// treasury's.
+    fn fee_account(counterparty: Counterparty, asset_id: &AssetId) -> AccountId;
+}
+
+/// Keeps the fees in the `AccountPurpose::Fees` account of the counterparty.
+impl<AccountId: Encode + Decode, AssetId> AssetFeeHandler<AccountId, AssetId>
+    for IndexedSovereignAccounts
+{
+    fn fee_account(counterparty: Counterparty, _: &AssetId) -> AccountId {
+        <Self as SovereignAccountOf<AccountId>>::sovereign_account(
+            counterparty,
+            AccountPurpose::Fees,
+        )
+    }
+}
+
+/// Number of buckets the window of a `RateLimit` is tracked in, bounding `RecentOutflows`.
+pub const OUTFLOW_BUCKETS: u32 = 16;
+
+/// Maximum amount of an asset transferred out of this parachain over a rolling window.
+#[derive(Clone, Copy, Decode, Default, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct RateLimit<Balance, BlockNumber> {
+    /// Maximum amount transferred within the window.
+    pub amount: Balance,
+    /// Number of blocks of the window, ending with the current block.
+    pub window: BlockNumber,
+}
+
+/// Maximum amount of an asset an account transfers out of this parachain per period.
+#[derive(Clone, Copy, Decode, Default, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Quota<Balance, BlockNumber> {
+    /// Maximum amount transferred per period.
+    pub amount: Balance,
+    /// Number of blocks of a period, periods start at multiples of it.
+    pub period: BlockNumber,
+}
+
+/// A transfer above the `DelayThresholds` of its asset, held in escrow until executed.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct PendingTransfer<AccountId, AssetId, Balance, BlockNumber> {
+    /// The sender of the transfer.
+    pub from: AccountId,
+    /// The chain the transfer is sent to.
+    pub counterparty: Counterparty,
+    /// The local asset, or Currency when `None`.
+    pub asset_id: Option<AssetId>,
+    /// The receiver of the transfer on the counterparty.
+    pub dest: AccountId,
+    /// The transferred amount.
+    pub amount: Balance,
+    /// Whether the Relay Chain transfer must not reap this parachain's account there.
+    pub keep_alive: bool,
+    /// The block the transfer is executed in.
+    pub execute_at: BlockNumber,
+}
+
+/// An inbound credit above the `QuarantineThresholds` of its asset, held in the account of its
+/// counterparty until released.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct QuarantinedCredit<AccountId, AssetId, Balance, BlockNumber> {
+    /// The chain the credit was received from.
+    pub counterparty: Counterparty,
+    /// The beneficiary of the credit.
+    pub dest: AccountId,
+    /// The local asset, or Currency when `None`.
+    pub asset_id: Option<AssetId>,
+    /// The credited amount.
+    pub amount: Balance,
+    /// The block the credit is released in unless returned before.
+    pub release_at: BlockNumber,
+}
+
+/// How XCMP messages reach a destination parachain.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum XCMPTransport {
+    /// Sent with `XCMPMessageSender` over a direct channel to the parachain.
+    Direct,
+    /// Sent as an upward message asking the Relay Chain to forward it to the parachain, for
+    /// parachains we have no direct channel with.
+    RelayRouted,
+}
+
+impl Default for XCMPTransport {
+    fn default() -> Self {
+        XCMPTransport::Direct
+    }
+}
+
+/// The message executing an outbound transfer checked by `prepare_transfer`.
+enum OutboundTransfer<UpwardMessage> {
+    /// Upward message transferring from this parachain's account on the Relay Chain.
+    Relay(UpwardMessage),
+    /// XCM message to the parachain.
+    Parachain(ParaId, OutboundXcm<UpwardMessage>),
+}
+
+/// An XCM message to a parachain, ready to be sent over its `XCMPTransport`.
+enum OutboundXcm<UpwardMessage> {
+    /// Sent with `XCMPMessageSender` to the parachain.
+    Direct(ParaId, VersionedXcm),
+    /// Sent as an upward message for the Relay Chain to forward.
+    RelayRouted(UpwardMessage),
+}
+
+/// Payload of the `DownwardMessage::Opaque` messages handled by this pallet.
 #[derive(Encode, Decode)]
-pub enum XCMPMessage<XAccountId, XBalance, XAssetIdOf> {
-    /// Transfer tokens to the given account from the Parachain account.
-    TransferToken(XAccountId, XBalance, Option<XAssetIdOf>),
+pub enum VersionedDownwardPayload<XAccountId, XBalance, XAssetIdOf> {
+    /// First version of the payload.
+    V0(DownwardPayload<XAccountId, XBalance, XAssetIdOf>),
+}
+
+/// Represent requests of the Relay Chain to this pallet
+#[derive(Encode, Decode)]
+pub enum DownwardPayload<XAccountId, XBalance, XAssetIdOf> {
+    /// Transfer assets (or Currency when `None`) to each account from the RelayAccount.
+    Credit(Vec<(XAccountId, Option<XAssetIdOf>, XBalance)>),
 }
 
 /// Configuration trait of this pallet.
@@ -93,52 +445,473 @@ pub trait Trait: frame_system::Trait + assets::Trait {
     type UpwardMessageSender: UpwardMessageSender<Self::UpwardMessage>;
 
     /// The upward message type used by the Parachain runtime.
-    type UpwardMessage: codec::Codec + BalancesMessage<Self::AccountId, BalanceOf<Self>>;
+    type UpwardMessage: codec::Codec
+        + BalancesMessage<Self::AccountId, BalanceOf<Self>>
+        + upward_messages::XCMPMessage;
 
     /// The sender of XCMP messages.
-    type XCMPMessageSender: XCMPMessageSender<
-        XCMPMessage<Self::AccountId, BalanceOf<Self>, AssetIdOf<Self>>,
-    >;
+    type XCMPMessageSender: XCMPMessageSender<VersionedXcm>;
     /// The currency type that uses Currency Trait in frame_support
     type Currency: Currency<Self::AccountId>;
+
+    /// Origin of the upward messages transferring tokens out of this parachain's account on the
+    /// Relay Chain.
+    type RelayTransferOrigin: Get<UpwardMessageOrigin>;
+
+    /// Origin of the upward messages asking the Relay Chain to forward XCMP messages.
+    type XCMPRoutingOrigin: Get<UpwardMessageOrigin>;
+
+    /// Id of this parachain; assets whose location starts with its `Parachain` junction are
+    /// reserved here and sent to other parachains with `ReserveAssetDeposit`.
+    type SelfParaId: Get<ParaId>;
+
+    /// Existential deposit of the Relay Chain, left in this parachain's account there by
+    /// `withdraw_all_from_relay_chain` with `keep_alive`.
+    type RelayExistentialDeposit: Get<BalanceOf<Self>>;
+
+    /// Share of a parachain's credit limit above which `CreditLimitApproached` is emitted.
+    type CreditLimitWarning: Get<Perbill>;
+
+    /// Derivation of the accounts holding the funds of the Relay Chain and sibling parachains.
+    type SovereignAccountOf: SovereignAccountOf<Self::AccountId>;
+
+    /// Derivation of the accounts before the last change of `SovereignAccountOf`, moved to the
+    /// current accounts by `migrate_sovereign_account`.
+    type PreviousSovereignAccountOf: SovereignAccountOf<Self::AccountId>;
+
+    /// Handling of inbound amounts too small to create the beneficiary's account.
+    type DustPolicy: Get<DustPolicy<Self::AccountId>>;
+
+    /// The minimum balance of an account holding an asset of pallet-assets; inbound amounts
+    /// below it are handled by the `DustPolicy` if the beneficiary holds none of the asset.
+    type AssetMinimumBalance: Get<BalanceOf<Self>>;
+
+    /// Handler of the transfer fees, e.g. the treasury.
+    type FeeHandler: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+    /// Handler of the transfer fees paid in assets, see `FeeRateOracle`.
+    type AssetFeeHandler: AssetFeeHandler<Self::AccountId, Self::AssetId>;
+
+    /// Rates at which fees are paid in assets without a rate set by governance.
+    type FeeRateOracle: FeeRateOracle<Self::AssetId>;
+
+    /// Origin allowed to configure the pallet and to act on delayed transfers and quarantined
+    /// credits, e.g. root, a council collective or a technical committee.
+    type AdminOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Origin allowed to move funds held by this parachain: its account on the Relay Chain, its
+    /// sovereign accounts and quarantined credits sent elsewhere. It should be stricter than
+    /// `AdminOrigin`, e.g. root.
+    type FundsOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Origin allowed to pause and unpause transfers, in addition to `AdminOrigin`.
+    type PauseOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Number of blocks transfers above the `DelayThresholds` of their asset are delayed for,
+    /// at least one.
+    type TransferDelay: Get<Self::BlockNumber>;
+
+    /// Maximum number of delayed transfers executed in a block; further transfers are executed
+    /// in the next block with room.
+    type MaxDelayedTransfers: Get<u32>;
+
+    /// Weight of executing a delayed transfer in `on_initialize`, charged to the transfer calls
+    /// as any of them may be delayed.
+    type DelayedTransferWeight: Get<Weight>;
+
+    /// Compliance checks of outbound transfers and inbound credits; rejected inbound amounts
+    /// are parked for their beneficiary to `claim` once allowed.
+    type TransferFilter: TransferFilter<Self::AccountId, Self::AssetId, BalanceOf<Self>>;
+
+    /// Number of blocks inbound credits above the `QuarantineThresholds` of their asset are
+    /// held for review, at least one.
+    type QuarantinePeriod: Get<Self::BlockNumber>;
+
+    /// Maximum number of quarantined credits released in a block; further credits are released
+    /// in the next block with room.
+    type MaxQuarantineReleases: Get<u32>;
+
+    /// Weight of releasing a quarantined credit.
+    type QuarantineReleaseWeight: Get<Weight>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as TokenDealer {
+        /// Pallet and call indices used by `EncodedRelayCall` to encode Relay Chain calls; no
+        /// call is encoded until they are set.
+        pub RelayIndices get(fn relay_call_indices): Option<RelayCallIndices>;
+        /// Transport used to send XCMP messages to each parachain, `Direct` unless set.
+        pub XCMPTransports get(fn xcmp_transport) config(xcmp_transports):
+            map hasher(twox_64_concat) ParaId => XCMPTransport;
+        /// XCM location of each local asset, or Currency when `None`, as seen from a sibling
+        /// parachain, e.g. `X1(Parent)` for the Relay Chain token.
+        pub AssetLocations get(fn asset_location):
+            map hasher(blake2_128_concat) Option<T::AssetId> => Option<MultiLocation>;
+        /// Local asset, or Currency when `None`, registered for each XCM location.
+        pub LocationAssets get(fn location_asset):
+            map hasher(blake2_128_concat) MultiLocation => Option<Option<T::AssetId>>;
+        /// Amount of each asset, or Currency when `None`, received from each counterparty minus
+        /// the amount sent back to it, i.e. what this parachain's account on the counterparty is
+        /// known to hold.
+        pub Backing get(fn backing) config(backing):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => BalanceOf<T>;
+        /// Estimated balance of this parachain's account on the Relay Chain, increased by each
+        /// `TransferInto` and decreased by each transfer out of it.
+        pub RelayFloat get(fn relay_float) config(): BalanceOf<T>;
+        /// Total amount of each asset, or Currency when `None`, paid out from the account of
+        /// each counterparty.
+        pub TotalInflow get(fn total_inflow):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => BalanceOf<T>;
+        /// Total amount of each asset, or Currency when `None`, moved into the account of each
+        /// counterparty.
+        pub TotalOutflow get(fn total_outflow):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => BalanceOf<T>;
+        /// Balance of each asset, or Currency when `None`, the account of each counterparty held
+        /// before the flows recorded in `TotalInflow` and `TotalOutflow`; zero unless set.
+        pub OpeningBalances get(fn opening_balance) config(opening_balances):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => BalanceOf<T>;
+        /// Inbound amounts of each asset, or Currency when `None`, kept in the account of each
+        /// counterparty until they are enough to create the account of their beneficiary.
+        pub PendingCredits get(fn pending_credit):
+            double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) (Counterparty, Option<T::AssetId>)
+            => BalanceOf<T>;
+        /// Maximum difference between the `TotalOutflow` and `TotalInflow` of each asset, or
+        /// Currency when `None`, with each parachain; unlimited if not set.
+        pub CreditLimits get(fn credit_limit) config(credit_limits):
+            double_map hasher(twox_64_concat) ParaId, hasher(blake2_128_concat) Option<T::AssetId>
+            => Option<BalanceOf<T>>;
+        /// Fee charged for transfers of each asset, or Currency when `None`, to each
+        /// counterparty; free if not set.
+        pub TransferFees get(fn transfer_fee) config(transfer_fees):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => Option<TransferFee<BalanceOf<T>>>;
+        /// Units of each asset paid per unit of Currency for fees of transfers of the asset,
+        /// overriding the `FeeRateOracle`.
+        pub AssetFeeRates get(fn asset_fee_rate) config(asset_fee_rates):
+            map hasher(blake2_128_concat) T::AssetId => Option<FixedU128>;
+        /// Transfers paused in each direction, see `Module::is_paused`.
+        pub Paused get(fn paused):
+            double_map hasher(blake2_128_concat) PauseScope<T::AssetId>, hasher(twox_64_concat) Direction
+            => bool;
+        /// Rate limit of the amount of each asset, or Currency when `None`, transferred to all
+        /// counterparties; unlimited if not set.
+        pub OutflowLimits get(fn outflow_limit) config(outflow_limits):
+            map hasher(blake2_128_concat) Option<T::AssetId>
+            => Option<RateLimit<BalanceOf<T>, T::BlockNumber>>;
+        /// Amount of each rate limited asset, or Currency when `None`, transferred out in each
+        /// bucket of blocks, by first block, overlapping its current window.
+        pub RecentOutflows get(fn recent_outflows):
+            map hasher(blake2_128_concat) Option<T::AssetId> => Vec<(T::BlockNumber, BalanceOf<T>)>;
+        /// Minimum amount of each asset, or Currency when `None`, transferred with each
+        /// counterparty; smaller inbound amounts are parked for their beneficiary to `claim` once
+        /// they add up to it.
+        pub MinimumTransfers get(fn minimum_transfer) config(minimum_transfers):
+            double_map hasher(blake2_128_concat) Counterparty, hasher(blake2_128_concat) Option<T::AssetId>
+            => BalanceOf<T>;
+        /// Amount of each asset, or Currency when `None`, above which transfers are delayed by
+        /// `TransferDelay` blocks; transfers are never delayed if not set.
+        pub DelayThresholds get(fn delay_threshold) config(delay_thresholds):
+            map hasher(blake2_128_concat) Option<T::AssetId> => Option<BalanceOf<T>>;
+        /// Delayed transfers by id.
+        pub PendingTransfers get(fn pending_transfer):
+            map hasher(twox_64_concat) u64
+            => Option<PendingTransfer<T::AccountId, T::AssetId, BalanceOf<T>, T::BlockNumber>>;
+        /// Ids of the delayed transfers executed in each block.
+        pub TransferQueue get(fn transfer_queue):
+            map hasher(twox_64_concat) T::BlockNumber => Vec<u64>;
+        /// Id of the next delayed transfer.
+        pub NextTransferId get(fn next_transfer_id): u64;
+        /// Latest block a delayed transfer was queued for.
+        pub LastTransferBlock get(fn last_transfer_block): T::BlockNumber;
+        /// Amount of each asset, or Currency when `None`, above which inbound credits are
+        /// quarantined for `QuarantinePeriod` blocks; credits are never quarantined if not set.
+        pub QuarantineThresholds get(fn quarantine_threshold) config(quarantine_thresholds):
+            map hasher(blake2_128_concat) Option<T::AssetId> => Option<BalanceOf<T>>;
+        /// Quarantined inbound credits by id.
+        pub Quarantine get(fn quarantined):
+            map hasher(twox_64_concat) u64
+            => Option<QuarantinedCredit<T::AccountId, T::AssetId, BalanceOf<T>, T::BlockNumber>>;
+        /// Ids of the quarantined credits released in each block.
+        pub QuarantineQueue get(fn quarantine_queue):
+            map hasher(twox_64_concat) T::BlockNumber => Vec<u64>;
+        /// Id of the next quarantined credit.
+        pub NextQuarantineId get(fn next_quarantine_id): u64;
+        /// Latest block a quarantined credit was queued for release in.
+        pub LastQuarantineBlock get(fn last_quarantine_block): T::BlockNumber;
+        /// Quota tier of each account, tier 0 unless set.
+        pub AccountTiers get(fn account_tier) config(account_tiers): map hasher(blake2_128_concat) T::AccountId => u8;
+        /// Quota of each asset, or Currency when `None`, for the accounts of each tier;
+        /// unlimited if not set.
+        pub TierQuotas get(fn tier_quota) config(tier_quotas):
+            double_map hasher(twox_64_concat) u8, hasher(blake2_128_concat) Option<T::AssetId>
+            => Option<Quota<BalanceOf<T>, T::BlockNumber>>;
+        /// Start of the quota period and amount of each asset, or Currency when `None`,
+        /// transferred out by each account within it.
+        pub AccountOutflows get(fn account_outflows):
+            double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) Option<T::AssetId>
+            => (T::BlockNumber, BalanceOf<T>);
+        /// Allowlisted and denylisted parachains, for each direction.
+        pub ParachainAccess get(fn parachain_access) config(parachain_access):
+            double_map hasher(twox_64_concat) ParaId, hasher(twox_64_concat) Direction
+            => Option<Access>;
+        /// Whether transfers in each direction are limited to allowlisted parachains.
+        pub AllowlistRequired get(fn allowlist_required) config(allowlist_required):
+            map hasher(twox_64_concat) Direction => bool;
+        /// Inbound amounts of each asset, or Currency when `None`, kept in the account of each
+        /// counterparty until claimed by their beneficiary with `claim`.
+        pub Claims get(fn claim_of):
+            double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) (Counterparty, Option<T::AssetId>)
+            => BalanceOf<T>;
+    }
+    add_extra_genesis {
+        /// Pallet and call indices used by `EncodedRelayCall`, unset when `None`.
+        config(relay_call_indices): Option<RelayCallIndices>;
+        /// XCM location of each local asset, or Currency when `None`, e.g. `X1(Parent)` for the
+        /// local asset representing the Relay Chain token.
+        config(asset_locations): Vec<(Option<T::AssetId>, MultiLocation)>;
+        build(|config: &GenesisConfig<T>| {
+            if let Some(indices) = config.relay_call_indices {
+                RelayIndices::put(indices);
+            }
+            for (asset_id, location) in config.asset_locations.iter() {
+                assert!(
+                    AssetLocations::<T>::get(asset_id).is_none()
+                        && LocationAssets::<T>::get(location).is_none(),
+                    "assets and their locations must be unique"
+                );
+                AssetLocations::<T>::insert(asset_id, location.clone());
+                LocationAssets::<T>::insert(location.clone(), asset_id);
+            }
+        });
+    }
 }
 
 decl_event! {
     pub enum Event<T> where
         AssetId = AssetIdOf<T>,
         AccountId = <T as frame_system::Trait>::AccountId,
-        Balance = BalanceOf<T>
+        Balance = BalanceOf<T>,
+        BlockNumber = <T as frame_system::Trait>::BlockNumber,
+        Hash = <T as frame_system::Trait>::Hash
     {
         /// Transferred tokens to the account on the relay chain.
-        /// (sender_accont_local, asset_id_local, reciever_account_on_relay_chain, transfer_amount)
-        TransferredTokensToRelayChain(AccountId, Option<AssetId>, AccountId, Balance),
+        /// The fee is in the transferred asset if it has a fee rate, in Currency otherwise.
+        /// (sender_accont_local, asset_id_local, reciever_account_on_relay_chain, transfer_amount, fee)
+        TransferredTokensToRelayChain(AccountId, Option<AssetId>, AccountId, Balance, Balance),
         /// Transferred tokens to the account on the parachain.
-        /// (sender_account_local, asset_id_local, para_id_dest, reciever_account_dest, asset_id_dest, transfer_amount,)
-        TransferredTokensToParachain(AccountId, Option<AssetId>, ParaId, AccountId, Option<AssetId>, Balance),
+        /// The fee is in the transferred asset if it has a fee rate, in Currency otherwise.
+        /// (sender_account_local, asset_id_local, para_id_dest, reciever_account_dest, transfer_amount, fee)
+        TransferredTokensToParachain(AccountId, Option<AssetId>, ParaId, AccountId, Balance, Balance),
         /// Transferred tokens to the account on request from the relay chain.
         /// (reciever_account_local, amount, Option<AssetId>, result)
         TransferredTokensFromRelayChain(AccountId, Balance, Option<AssetId>, DispatchResult),
         /// Transferred tokens to the account on request from parachain.
         /// (ParaId, reciever_account_on_para, amount, assetId, result)
         TransferredTokensViaXCMP(ParaId, AccountId, Balance, Option<AssetId>, DispatchResult),
+        /// Updated the indices used to encode Relay Chain calls.
+        /// (relay_call_indices)
+        RelayCallIndicesUpdated(RelayCallIndices),
+        /// Updated the transport used to send XCMP messages to a parachain.
+        /// (para_id, transport)
+        XCMPTransportUpdated(ParaId, XCMPTransport),
+        /// Transferred tokens from this parachain's account to the account on the relay chain,
+        /// dispatched with the parachain origin.
+        /// (reciever_account_on_relay_chain, transfer_amount)
+        TreasuryTransferredToRelayChain(AccountId, Balance),
+        /// Transferred the whole balance of this parachain's account on the relay chain to the
+        /// account there, dispatched with the parachain origin.
+        /// (reciever_account_on_relay_chain, keep_alive)
+        WithdrewAllFromRelayChain(AccountId, bool),
+        /// Received a downward message this pallet does not understand.
+        /// (message_hash)
+        UnrecognisedDownwardMessage(Hash),
+        /// Set the credit limit of an asset with a parachain, removed when `None`.
+        /// (ParaId, asset_id_local, credit_limit)
+        CreditLimitUpdated(ParaId, Option<AssetId>, Option<Balance>),
+        /// The net amount of an asset transferred with a parachain is close to its credit limit.
+        /// (ParaId, asset_id_local, net_amount, credit_limit)
+        CreditLimitApproached(ParaId, Option<AssetId>, Balance, Balance),
+        /// Moved the funds of a counterparty from an account of a previous derivation scheme to
+        /// its current account.
+        /// (counterparty, purpose, old_account, new_account)
+        SovereignAccountMigrated(Counterparty, AccountPurpose, AccountId, AccountId),
+        /// Kept an inbound amount too small to create the account of its beneficiary until more
+        /// is received.
+        /// (reciever_account_local, counterparty, asset_id_local, pending_amount)
+        CreditPending(AccountId, Counterparty, Option<AssetId>, Balance),
+        /// Transferred an inbound amount too small to create the account of its beneficiary to
+        /// the dust account.
+        /// (reciever_account_local, asset_id_local, amount, dust_account)
+        DustRouted(AccountId, Option<AssetId>, Balance, AccountId),
+        /// Set the fee of transfers of an asset to a counterparty, removed when `None`.
+        /// (counterparty, asset_id_local, fee)
+        TransferFeeUpdated(Counterparty, Option<AssetId>, Option<TransferFee<Balance>>),
+        /// Set the rate at which fees of transfers of an asset are paid in it, removed when
+        /// `None`.
+        /// (asset_id_local, fee_rate)
+        AssetFeeRateUpdated(AssetId, Option<FixedU128>),
+        /// Paused transfers in a direction.
+        /// (scope, direction)
+        TransfersPaused(PauseScope<AssetId>, Direction),
+        /// Resumed transfers in a direction.
+        /// (scope, direction)
+        TransfersUnpaused(PauseScope<AssetId>, Direction),
+        /// Set the rate limit of transfers of an asset out of this parachain, removed when `None`.
+        /// (asset_id_local, rate_limit)
+        OutflowLimitUpdated(Option<AssetId>, Option<RateLimit<Balance, BlockNumber>>),
+        /// Set the amount of an asset above which transfers are delayed, removed when `None`.
+        /// (asset_id_local, delay_threshold)
+        DelayThresholdUpdated(Option<AssetId>, Option<Balance>),
+        /// Held a transfer in escrow until the block it is executed in.
+        /// (transfer_id, sender_account_local, counterparty, asset_id_local, reciever_account_dest, transfer_amount, execute_at)
+        TransferQueued(u64, AccountId, Counterparty, Option<AssetId>, AccountId, Balance, BlockNumber),
+        /// Executed a delayed transfer, its funds are returned to the sender if it failed.
+        /// (transfer_id, result)
+        DelayedTransferExecuted(u64, DispatchResult),
+        /// The sender cancelled a delayed transfer, its funds are returned to the sender.
+        /// (transfer_id)
+        DelayedTransferCancelled(u64),
+        /// Governance vetoed a delayed transfer, its funds are returned to the sender.
+        /// (transfer_id)
+        DelayedTransferVetoed(u64),
+        /// Set the amount of an asset above which inbound credits are quarantined, removed when
+        /// `None`.
+        /// (asset_id_local, quarantine_threshold)
+        QuarantineThresholdUpdated(Option<AssetId>, Option<Balance>),
+        /// Held an inbound credit in the account of its counterparty until the block it is
+        /// released in.
+        /// (quarantine_id, reciever_account_local, counterparty, asset_id_local, amount, release_at)
+        CreditQuarantined(u64, AccountId, Counterparty, Option<AssetId>, Balance, BlockNumber),
+        /// Released a quarantined credit, it is parked for its beneficiary to claim if it failed.
+        /// (quarantine_id, result)
+        QuarantineReleased(u64, DispatchResult),
+        /// Returned a quarantined credit to the account on its counterparty.
+        /// (quarantine_id, reciever_account_on_counterparty)
+        QuarantineReturned(u64, AccountId),
+        /// Set the minimum amount of an asset transferred with a counterparty.
+        /// (counterparty, asset_id_local, minimum_amount)
+        MinimumTransferUpdated(Counterparty, Option<AssetId>, Balance),
+        /// Set the quota of an asset for the accounts of a tier, removed when `None`.
+        /// (tier, asset_id_local, quota)
+        TierQuotaUpdated(u8, Option<AssetId>, Option<Quota<Balance, BlockNumber>>),
+        /// Set the quota tier of an account.
+        /// (account, tier)
+        AccountTierUpdated(AccountId, u8),
+        /// Listed a parachain in the allowlist or denylist of a direction, unlisted when `None`.
+        /// (ParaId, direction, access)
+        ParachainAccessUpdated(ParaId, Direction, Option<Access>),
+        /// Set whether transfers in a direction are limited to allowlisted parachains.
+        /// (direction, required)
+        AllowlistRequirementUpdated(Direction, bool),
+        /// Ignored an XCMP message from a parachain inbound transfers are not permitted with.
+        /// (ParaId, message_hash)
+        XCMPMessageRejected(ParaId, Hash),
+        /// Kept an inbound amount in the account of its counterparty for its beneficiary to claim.
+        /// (reciever_account_local, counterparty, asset_id_local, claimable_amount)
+        CreditParked(AccountId, Counterparty, Option<AssetId>, Balance),
+        /// Paid out a parked inbound amount to its beneficiary.
+        /// (reciever_account_local, counterparty, asset_id_local, amount)
+        Claimed(AccountId, Counterparty, Option<AssetId>, Balance),
+        /// Corrected the estimated balance of this parachain's account on the relay chain.
+        /// (relay_float)
+        RelayFloatUpdated(Balance),
+        /// Received an XCMP message this pallet does not understand, nothing was transferred.
+        /// (ParaId, message_hash)
+        UnrecognisedXCMPMessage(ParaId, Hash),
+        /// Registered the XCM location of a local asset, or Currency when `None`.
+        /// (asset_id_local, location)
+        AssetLocationRegistered(Option<AssetId>, MultiLocation),
+        /// Removed the XCM location of a local asset, or Currency when `None`.
+        /// (asset_id_local)
+        AssetLocationDeregistered(Option<AssetId>),
+        /// Set the opening balance of the account of a counterparty in an asset.
+        /// (counterparty, asset_id_local, opening_balance)
+        OpeningBalanceUpdated(Counterparty, Option<AssetId>, Balance),
+    }
+}
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The upward message type cannot encode this Relay Chain call, e.g. the indices of
+        /// `EncodedRelayCall` are not set.
+        UnsupportedRelayCall,
+        /// The asset has no registered XCM location.
+        AssetNotRegistered,
+        /// The XCM location is already registered for another asset.
+        LocationAlreadyRegistered,
+        /// The account cannot be represented as an XCM `AccountId32`.
+        UnsupportedAccount,
+        /// The amount does not fit in an XCM asset amount.
+        AmountOverflow,
+        /// The transfer exceeds what was received from the destination chain, so this
+        /// parachain's account there may not cover it.
+        InsufficientBacking,
+        /// The transfer exceeds the estimated balance of this parachain's account on the Relay
+        /// Chain.
+        InsufficientRelayFloat,
+        /// The transfer would take the net amount transferred with the parachain over its credit
+        /// limit.
+        CreditLimitExceeded,
+        /// The sender cannot pay both the transfer and its fee.
+        InsufficientBalance,
+        /// Transfers of the asset with the counterparty are paused in this direction.
+        TransfersPaused,
+        /// The account has nothing to claim.
+        NothingToClaim,
+        /// There are no pending credits for the account.
+        NoPendingCredit,
+        /// The pending credits are still too small to be paid out under the `DustPolicy`.
+        CreditStillPending,
+        /// Transfers to the parachain are not permitted.
+        ParachainNotPermitted,
+        /// The transfer would exceed the amount of the asset allowed out of this parachain within
+        /// its rate limit window.
+        OutflowRateLimitExceeded,
+        /// There is no delayed transfer with this id.
+        UnknownTransfer,
+        /// Only the sender of a delayed transfer can cancel it.
+        NotTransferSender,
+        /// The transfer is rejected by the `TransferFilter`.
+        TransferFiltered,
+        /// There is no quarantined credit with this id.
+        UnknownQuarantine,
+        /// The amount is below the minimum transferred with the counterparty.
+        BelowMinimumTransfer,
+        /// The transfer would exceed the sender's quota of the asset for the current period.
+        QuotaExceeded,
+        /// The previous and current derivations give the same sovereign account.
+        SovereignAccountUnchanged,
     }
 }
 
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
         /// Transfer `amount` of tokens (local asset_id) or Currency from Parachain account to the Relay Chain
         /// at the given `dest` account.
-        #[weight = 10]
+        #[weight = 10 + T::DelayedTransferWeight::get()]
         pub fn transfer_tokens_to_relay_chain(origin, dest: T::AccountId, amount: BalanceOf<T>, asset_id: Option<AssetIdOf<T>>) {
             let who = ensure_signed(origin)?;
             Self::make_transfer_to_relay_chain(&asset_id, &who, &dest, amount)?;
-            Self::deposit_event(Event::<T>::TransferredTokensToRelayChain(who, asset_id, dest, amount));
+        }
+
+        /// Same as `transfer_tokens_to_relay_chain`, but the Relay Chain transfer fails rather
+        /// than reaping this parachain's account on the Relay Chain.
+        #[weight = 10 + T::DelayedTransferWeight::get()]
+        pub fn transfer_tokens_to_relay_chain_keep_alive(origin, dest: T::AccountId, amount: BalanceOf<T>, asset_id: Option<AssetIdOf<T>>) {
+            let who = ensure_signed(origin)?;
+            Self::transfer_or_queue(Counterparty::Relay, &asset_id, &who, &dest, amount, true)?;
         }
 
         /// Transfer `amount` of tokens (local asset_id) or Currency to another parachain at the
-        /// give `dest` account. If the other parachain has assets and needs to be mapped, use the
-        /// Module fn `make_transfer_to_parachain` instead.
-        #[weight = 10]
+        /// give `dest` account. The asset is identified to the other parachain by its registered
+        /// XCM location.
+        #[weight = 10 + T::DelayedTransferWeight::get()]
         pub fn transfer_assets_to_parachain_chain(
             origin,
             para_id: u32,
@@ -149,167 +922,1688 @@ decl_module! {
             let who = ensure_signed(origin)?;
 
             let para_id: ParaId = para_id.into();
-            Self::make_transfer_to_parachain(&who, &asset_id, para_id, &dest, &asset_id, amount)?;
-            Self::deposit_event(Event::<T>::TransferredTokensToParachain(who, asset_id, para_id, dest, asset_id, amount ));
+            Self::make_transfer_to_parachain(&who, &asset_id, para_id, &dest, amount)?;
         }
 
-        fn deposit_event() = default;
-    }
-}
-
-/// This is a hack to convert from one generic type to another where we are sure that both are the
-/// same type/use the same encoding.
-fn convert_hack<O: Decode>(input: &impl Encode) -> O {
-    input.using_encoded(|e| Decode::decode(&mut &e[..]).expect("Must be compatible; qed"))
-}
+        /// Cancel a delayed transfer of the sender, returning its funds.
+        #[weight = 10]
+        pub fn cancel_transfer(origin, id: u64) {
+            let who = ensure_signed(origin)?;
+            let transfer = Self::pending_transfer(id).ok_or(Error::<T>::UnknownTransfer)?;
+            ensure!(transfer.from == who, Error::<T>::NotTransferSender);
+            Self::unqueue_transfer(id, transfer)?;
+            Self::deposit_event(Event::<T>::DelayedTransferCancelled(id));
+        }
 
-impl<T: Trait> Module<T> {
-    /// Transfer Asset(asset_id == Some(id)) or Currency (asset_id == None) to the Relay Chain;
-    /// This transfers Asset/Currency from this Parachain's account to the RelayAccount on this
-    /// parachain and sends an upward message to the relay chain
-    ///
-    /// WARN: Must ensure parachain account on relay chain has enough balance to transfer out
-    /// from, this does not guarentee that Relay Chain `dest` account is credited.
-    pub fn make_transfer_to_relay_chain(
-        asset_id: &Option<AssetIdOf<T>>,
-        from: &T::AccountId,
-        dest: &T::AccountId,
-        amount: BalanceOf<T>,
-    ) -> DispatchResult {
-        let relay_account: T::AccountId = RelayAccount::default().into_account();
+        /// Veto a delayed transfer, returning its funds to its sender.
+        #[weight = 10]
+        pub fn veto_transfer(origin, id: u64) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let transfer = Self::pending_transfer(id).ok_or(Error::<T>::UnknownTransfer)?;
+            Self::unqueue_transfer(id, transfer)?;
+            Self::deposit_event(Event::<T>::DelayedTransferVetoed(id));
+        }
 
-        if let Some(id) = asset_id {
-            let amount = convert_hack(&amount);
-            <assets::Module<T>>::make_transfer(from, *id, &relay_account, amount)?;
-        } else {
-            // Transfer parachain asset to the relay_account (which is on this parachain)
-            T::Currency::transfer(
-                from,
-                &relay_account,
-                amount,
-                ExistenceRequirement::KeepAlive,
-            )?;
+        /// Quarantine inbound credits of an asset, or Currency when `asset_id` is `None`, above
+        /// `threshold` for `QuarantinePeriod` blocks; `None` removes the threshold.
+        #[weight = 10]
+        pub fn set_quarantine_threshold(origin, asset_id: Option<AssetIdOf<T>>, threshold: Option<BalanceOf<T>>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match threshold {
+                Some(threshold) => QuarantineThresholds::<T>::insert(asset_id, threshold),
+                None => QuarantineThresholds::<T>::remove(asset_id),
+            }
+            Self::deposit_event(Event::<T>::QuarantineThresholdUpdated(asset_id, threshold));
         }
 
-        // Send upward message to Relay Chain to transfer `amount` from this parachain's
-        // account on the relay chain to dest account.
-        let msg = <T::UpwardMessage>::transfer(dest.clone(), amount);
-        <T as Trait>::UpwardMessageSender::send_upward_message(&msg, UpwardMessageOrigin::Signed)
-            .expect("Should not fail; qed");
-        Ok(())
-    }
+        /// Release a quarantined credit to its beneficiary before the end of its review period.
+        #[weight = 10 + T::QuarantineReleaseWeight::get()]
+        pub fn release_quarantined(origin, id: u64) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let credit = Self::quarantined(id).ok_or(Error::<T>::UnknownQuarantine)?;
+            Self::unquarantine(id, &credit);
+            Self::release_credit(id, credit);
+        }
 
-    /// Transfer Asset(asset_id == Some(id)) or Currency (asset_id == None) to a dest Parachain at
-    /// para_id;
-    /// This transfers Asset/Currency from this Parachain's account to the dest Parachain's
-    /// account, derived from their para_id, on this parachain and sends an XCMP message to dest
-    /// parachain
-    /// INFO: If the other parachain has assets, use `dest_asset_id` to inform other parachain
-    /// which asset_id to complete the transfer.
-    ///
-    /// WARN: Must ensure that this parachain account on the dest parachain has enough balance to transfer out
-    /// from, this function does not guarentee that dest parachain `dest` account is credited.
-    pub fn make_transfer_to_parachain(
-        from: &T::AccountId,
-        asset_id: &Option<AssetIdOf<T>>,
-        para_id: ParaId,
-        dest: &T::AccountId,
-        dest_asset_id: &Option<AssetIdOf<T>>,
-        amount: BalanceOf<T>,
-    ) -> DispatchResult {
-        let para_account: T::AccountId = para_id.into_account();
+        /// Return a quarantined credit to the `dest` account on the chain it was received from,
+        /// as the sender of the credit is not known.
+        #[weight = 10]
+        pub fn return_quarantined(origin, id: u64, dest: T::AccountId) {
+            T::FundsOrigin::ensure_origin(origin)?;
+            let credit = Self::quarantined(id).ok_or(Error::<T>::UnknownQuarantine)?;
+            match credit.counterparty {
+                Counterparty::Relay => {
+                    let msg = <T::UpwardMessage>::transfer(dest.clone(), credit.amount)
+                        .ok_or(Error::<T>::UnsupportedRelayCall)?;
+                    Self::withdraw_relay_float(credit.amount)?;
+                    Self::send_upward_message(&msg, T::RelayTransferOrigin::get());
+                }
+                Counterparty::Parachain(para_id) => {
+                    let msg = Self::transfer_xcm(&credit.asset_id, credit.amount, &dest)?;
+                    Self::send_xcm(Self::prepare_xcm(para_id, msg)?);
+                }
+            }
+            Backing::<T>::mutate(credit.counterparty, credit.asset_id, |backing| {
+                *backing = backing.saturating_sub(credit.amount)
+            });
+            Self::unquarantine(id, &credit);
+            Self::deposit_event(Event::<T>::QuarantineReturned(id, dest));
+        }
 
-        if let Some(id) = asset_id {
-            <assets::Module<T>>::make_transfer(from, *id, &para_account, convert_hack(&amount))?;
-        } else {
-            T::Currency::transfer(from, &para_account, amount, ExistenceRequirement::KeepAlive)?;
+        /// Delay transfers of an asset, or Currency when `asset_id` is `None`, above `threshold`
+        /// by `TransferDelay` blocks; `None` removes the threshold.
+        #[weight = 10]
+        pub fn set_delay_threshold(origin, asset_id: Option<AssetIdOf<T>>, threshold: Option<BalanceOf<T>>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match threshold {
+                Some(threshold) => DelayThresholds::<T>::insert(asset_id, threshold),
+                None => DelayThresholds::<T>::remove(asset_id),
+            }
+            Self::deposit_event(Event::<T>::DelayThresholdUpdated(asset_id, threshold));
         }
 
-        // Send XCMPMessage to the other parachain
-        T::XCMPMessageSender::send_xcmp_message(
-            para_id,
-            &XCMPMessage::TransferToken(dest.clone(), amount, *dest_asset_id),
-        )
-        .expect("Should not fail; qed");
-        Ok(())
-    }
-}
+        /// Pay out the inbound amounts of an asset, or Currency when `asset_id` is `None`,
+        /// received from `counterparty` for the sender and parked while inbound transfers were
+        /// paused, for being below the minimum transfer, for being rejected by the
+        /// `TransferFilter` or for exceeding the credit limit. Fails until they add up to the
+        /// minimum transfer and are within the credit limit.
+        #[weight = 10]
+        pub fn claim(origin, counterparty: Counterparty, asset_id: Option<AssetIdOf<T>>) {
+            let who = ensure_signed(origin)?;
+            ensure!(
+                !Self::is_paused(counterparty, &asset_id, Direction::Inbound),
+                Error::<T>::TransfersPaused
+            );
+            let key = (counterparty, asset_id);
+            let amount = Self::claim_of(&who, key);
+            ensure!(!amount.is_zero(), Error::<T>::NothingToClaim);
+            ensure!(
+                amount >= Self::minimum_transfer(counterparty, &asset_id),
+                Error::<T>::BelowMinimumTransfer
+            );
+            ensure!(
+                T::TransferFilter::allow_inbound(counterparty, &who, &asset_id, amount),
+                Error::<T>::TransferFiltered
+            );
+            Self::ensure_credit_limit(counterparty, &asset_id, Zero::zero(), amount)?;
+            Self::credit(counterparty, &asset_id, &who, amount)?;
+            Claims::<T>::remove(&who, key);
+            Self::note_credit_exposure(counterparty, &asset_id);
+            Self::deposit_event(Event::<T>::Claimed(who, counterparty, asset_id, amount));
+        }
 
-impl<T: Trait> DownwardMessageHandler for Module<T> {
-    /// Handles messages from the Relay Chain, only match to `TransferInto` type
-    /// Here we use the remark field of the downward message to decode into Option<AssetId>
-    fn handle_downward_message(msg: &DownwardMessage) {
-        #[allow(clippy::clippy::single_match)]
-        match msg {
-            DownwardMessage::TransferInto(dest, relay_amount, remark) => {
-                let dest: T::AccountId = convert_hack(&dest);
-                let relay_amount: BalanceOf<T> = convert_hack(relay_amount);
-                let relay_account = RelayAccount::default().into_account();
-                // remark has a concerte type [u8; 32]
-                let asset_id: Option<AssetIdOf<T>> = convert_hack(remark);
+        /// Pay out the credits of an asset, or Currency when `asset_id` is `None`, received from
+        /// `counterparty` and pending for `dest`, once `dest` holds the asset or the `DustPolicy`
+        /// routes them, e.g. after the policy changed from `Accumulate` to `RouteTo`.
+        #[weight = 10]
+        pub fn release_pending_credit(
+            origin,
+            dest: T::AccountId,
+            counterparty: Counterparty,
+            asset_id: Option<AssetIdOf<T>>,
+        ) {
+            ensure_signed(origin)?;
+            let total = Self::pending_credit(&dest, (counterparty, asset_id));
+            ensure!(!total.is_zero(), Error::<T>::NoPendingCredit);
+            ensure!(
+                Self::credit_destination(&asset_id, &dest, total).is_some(),
+                Error::<T>::CreditStillPending
+            );
+            Self::credit(counterparty, &asset_id, &dest, Zero::zero())?;
+        }
 
-                let res = match asset_id {
-                    Some(id) => <assets::Module<T>>::make_transfer(
-                        &relay_account,
-                        id,
-                        &dest,
-                        convert_hack(&relay_amount.clone()),
-                    ),
-                    None => T::Currency::transfer(
-                        &relay_account,
-                        &dest,
-                        relay_amount,
-                        ExistenceRequirement::KeepAlive,
-                    ),
-                };
+        /// Limit the amount of an asset, or Currency when `asset_id` is `None`, transferred out
+        /// of this parachain within a rolling window of blocks; `None` removes the limit.
+        #[weight = 10]
+        pub fn set_outflow_limit(
+            origin,
+            asset_id: Option<AssetIdOf<T>>,
+            limit: Option<RateLimit<BalanceOf<T>, T::BlockNumber>>,
+        ) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match limit {
+                Some(limit) => OutflowLimits::<T>::insert(asset_id, limit),
+                None => {
+                    OutflowLimits::<T>::remove(asset_id);
+                    RecentOutflows::<T>::remove(asset_id);
+                }
+            }
+            Self::deposit_event(Event::<T>::OutflowLimitUpdated(asset_id, limit));
+        }
 
-                Self::deposit_event(Event::<T>::TransferredTokensFromRelayChain(
-                    dest,
-                    relay_amount,
-                    asset_id,
-                    res,
-                ));
+        /// Set the minimum amount of an asset, or Currency when `asset_id` is `None`, transferred
+        /// with `counterparty`; zero removes the minimum. Smaller outbound transfers fail and
+        /// smaller inbound amounts are parked for their beneficiary to `claim`.
+        #[weight = 10]
+        pub fn set_minimum_transfer(
+            origin,
+            counterparty: Counterparty,
+            asset_id: Option<AssetIdOf<T>>,
+            amount: BalanceOf<T>,
+        ) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            if amount.is_zero() {
+                MinimumTransfers::<T>::remove(counterparty, asset_id);
+            } else {
+                MinimumTransfers::<T>::insert(counterparty, asset_id, amount);
             }
-            _ => {}
+            Self::deposit_event(Event::<T>::MinimumTransferUpdated(counterparty, asset_id, amount));
         }
-    }
-}
 
-impl<T: Trait> XCMPMessageHandler<XCMPMessage<T::AccountId, BalanceOf<T>, AssetIdOf<T>>>
-    for Module<T>
-{
-    /// Handles messages from other parachains, only match to `TransferToken` message
-    fn handle_xcmp_message(
-        src: ParaId,
-        msg: &XCMPMessage<T::AccountId, BalanceOf<T>, AssetIdOf<T>>,
-    ) {
-        match msg {
-            XCMPMessage::TransferToken(dest, amount, asset_id) => {
-                let para_account = src.clone().into_account();
+        /// Set the balance of an asset, or Currency when `asset_id` is `None`, the account of
+        /// `counterparty` held before the flows processed by this pallet, e.g. when it was funded
+        /// before the pallet was deployed.
+        #[weight = 10]
+        pub fn set_opening_balance(
+            origin,
+            counterparty: Counterparty,
+            asset_id: Option<AssetIdOf<T>>,
+            balance: BalanceOf<T>,
+        ) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            OpeningBalances::<T>::insert(counterparty, asset_id, balance);
+            Self::deposit_event(Event::<T>::OpeningBalanceUpdated(counterparty, asset_id, balance));
+        }
 
-                let res = match asset_id {
-                    Some(id) => <assets::Module<T>>::make_transfer(
-                        &para_account,
-                        *id,
-                        &dest,
-                        convert_hack(&amount.clone()),
-                    ),
-                    None => T::Currency::transfer(
-                        &para_account,
-                        &dest,
-                        *amount,
-                        ExistenceRequirement::KeepAlive,
-                    ),
-                };
+        /// Limit the amount of an asset, or Currency when `asset_id` is `None`, each account of
+        /// `tier` transfers out of this parachain per period; `None` removes the quota.
+        #[weight = 10]
+        pub fn set_tier_quota(
+            origin,
+            tier: u8,
+            asset_id: Option<AssetIdOf<T>>,
+            quota: Option<Quota<BalanceOf<T>, T::BlockNumber>>,
+        ) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match quota {
+                Some(quota) => TierQuotas::<T>::insert(tier, asset_id, quota),
+                None => TierQuotas::<T>::remove(tier, asset_id),
+            }
+            Self::deposit_event(Event::<T>::TierQuotaUpdated(tier, asset_id, quota));
+        }
 
-                Self::deposit_event(Event::<T>::TransferredTokensViaXCMP(
-                    src,
-                    dest.clone(),
-                    *amount,
-                    *asset_id,
-                    res,
-                ));
+        /// Move `who` to the quota `tier`.
+        #[weight = 10]
+        pub fn set_account_tier(origin, who: T::AccountId, tier: u8) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            if tier == 0 {
+                AccountTiers::<T>::remove(&who);
+            } else {
+                AccountTiers::<T>::insert(&who, tier);
+            }
+            Self::deposit_event(Event::<T>::AccountTierUpdated(who, tier));
+        }
+
+        /// List the parachain at `para_id` in the allowlist or denylist of `direction`; `None`
+        /// unlists it.
+        #[weight = 10]
+        pub fn set_parachain_access(origin, para_id: u32, direction: Direction, access: Option<Access>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let para_id: ParaId = para_id.into();
+            match access {
+                Some(access) => ParachainAccess::insert(para_id, direction, access),
+                None => ParachainAccess::remove(para_id, direction),
             }
+            Self::deposit_event(Event::<T>::ParachainAccessUpdated(para_id, direction, access));
+        }
+
+        /// Limit transfers in `direction` to allowlisted parachains, or allow all but the
+        /// denylisted ones when not `required`.
+        #[weight = 10]
+        pub fn set_allowlist_required(origin, direction: Direction, required: bool) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            AllowlistRequired::insert(direction, required);
+            Self::deposit_event(Event::<T>::AllowlistRequirementUpdated(direction, required));
+        }
+
+        /// Pause transfers within `scope` in `direction`. Paused outbound transfers fail, paused
+        /// inbound transfers are parked for their beneficiary to `claim` once unpaused.
+        #[weight = 10]
+        pub fn pause(origin, scope: PauseScope<AssetIdOf<T>>, direction: Direction) {
+            Self::ensure_pause_origin(origin)?;
+            Paused::<T>::insert(scope, direction, true);
+            Self::deposit_event(Event::<T>::TransfersPaused(scope, direction));
+        }
+
+        /// Resume transfers within `scope` in `direction`; transfers also paused within another
+        /// scope stay paused.
+        #[weight = 10]
+        pub fn unpause(origin, scope: PauseScope<AssetIdOf<T>>, direction: Direction) {
+            Self::ensure_pause_origin(origin)?;
+            Paused::<T>::remove(scope, direction);
+            Self::deposit_event(Event::<T>::TransfersUnpaused(scope, direction));
+        }
+
+        /// Transfer `amount` of Relay Chain tokens from this parachain's account on the Relay Chain
+        /// to the `dest` account there, dispatched with the `Parachain` origin. Nothing is
+        /// settled on this parachain, this is meant for treasury operations.
+        #[weight = 10]
+        pub fn transfer_treasury_to_relay_chain(origin, dest: T::AccountId, amount: BalanceOf<T>) {
+            T::FundsOrigin::ensure_origin(origin)?;
+            let msg = <T::UpwardMessage>::transfer(dest.clone(), amount)
+                .ok_or(Error::<T>::UnsupportedRelayCall)?;
+            Self::withdraw_relay_float(amount)?;
+            Self::send_upward_message(&msg, UpwardMessageOrigin::Parachain);
+            Self::deposit_event(Event::<T>::TreasuryTransferredToRelayChain(dest, amount));
+        }
+
+        /// Transfer the whole balance of this parachain's account on the Relay Chain to the
+        /// `dest` account there, leaving the existential deposit if `keep_alive`, dispatched with
+        /// the `Parachain` origin. Nothing is settled on this parachain.
+        #[weight = 10]
+        pub fn withdraw_all_from_relay_chain(origin, dest: T::AccountId, keep_alive: bool) {
+            T::FundsOrigin::ensure_origin(origin)?;
+            let msg = <T::UpwardMessage>::transfer_all(dest.clone(), keep_alive)
+                .ok_or(Error::<T>::UnsupportedRelayCall)?;
+            Self::send_upward_message(&msg, UpwardMessageOrigin::Parachain);
+            if keep_alive {
+                RelayFloat::<T>::put(T::RelayExistentialDeposit::get());
+            } else {
+                RelayFloat::<T>::kill();
+            }
+            Self::deposit_event(Event::<T>::WithdrewAllFromRelayChain(dest, keep_alive));
+        }
+
+        /// Limit the difference between the amounts of an asset, or Currency when `asset_id` is
+        /// `None`, sent to and received from the parachain at `para_id`; `None` removes the
+        /// limit.
+        #[weight = 10]
+        pub fn set_credit_limit(origin, para_id: u32, asset_id: Option<AssetIdOf<T>>, limit: Option<BalanceOf<T>>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let para_id: ParaId = para_id.into();
+            match limit {
+                Some(limit) => CreditLimits::<T>::insert(para_id, asset_id, limit),
+                None => CreditLimits::<T>::remove(para_id, asset_id),
+            }
+            Self::deposit_event(Event::<T>::CreditLimitUpdated(para_id, asset_id, limit));
+        }
+
+        /// Move the whole balance of each of `asset_ids`, Currency for `None`, from the account
+        /// derived by `PreviousSovereignAccountOf` to the one derived by `SovereignAccountOf` for
+        /// `purpose` of `counterparty`, after the runtime changed its derivation scheme. Nothing
+        /// is moved unless every balance can be.
+        #[weight = 10]
+        pub fn migrate_sovereign_account(
+            origin,
+            counterparty: Counterparty,
+            purpose: AccountPurpose,
+            asset_ids: Vec<Option<AssetIdOf<T>>>,
+        ) {
+            T::FundsOrigin::ensure_origin(origin)?;
+            let old_account = T::PreviousSovereignAccountOf::sovereign_account(counterparty, purpose);
+            let new_account = Self::sovereign_account(counterparty, purpose);
+            ensure!(old_account != new_account, Error::<T>::SovereignAccountUnchanged);
+
+            let mut balances = Vec::new();
+            for asset_id in asset_ids {
+                if balances.iter().any(|(id, _)| *id == asset_id) {
+                    continue;
+                }
+                let balance = match asset_id {
+                    Some(id) => convert_hack(&<assets::Module<T>>::balance(id, old_account.clone())),
+                    None => {
+                        let balance = T::Currency::free_balance(&old_account);
+                        T::Currency::ensure_can_withdraw(
+                            &old_account,
+                            balance,
+                            WithdrawReason::Transfer.into(),
+                            Zero::zero(),
+                        )?;
+                        balance
+                    }
+                };
+                balances.push((asset_id, balance));
+            }
+
+            for (asset_id, balance) in balances {
+                if balance.is_zero() {
+                    continue;
+                }
+                match asset_id {
+                    Some(id) => <assets::Module<T>>::make_transfer(
+                        &old_account,
+                        id,
+                        &new_account,
+                        convert_hack(&balance),
+                    )?,
+                    None => T::Currency::transfer(
+                        &old_account,
+                        &new_account,
+                        balance,
+                        ExistenceRequirement::AllowDeath,
+                    )?,
+                }
+            }
+            Self::deposit_event(Event::<T>::SovereignAccountMigrated(counterparty, purpose, old_account, new_account));
+        }
+
+        /// Set the fee charged for transfers of an asset, or Currency when `asset_id` is `None`,
+        /// to `counterparty`; `None` makes them free.
+        #[weight = 10]
+        pub fn set_transfer_fee(
+            origin,
+            counterparty: Counterparty,
+            asset_id: Option<AssetIdOf<T>>,
+            fee: Option<TransferFee<BalanceOf<T>>>,
+        ) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match fee {
+                Some(fee) => TransferFees::<T>::insert(counterparty, asset_id, fee),
+                None => TransferFees::<T>::remove(counterparty, asset_id),
+            }
+            Self::deposit_event(Event::<T>::TransferFeeUpdated(counterparty, asset_id, fee));
+        }
+
+        /// Set the units of `asset_id` paid per unit of Currency for the fees of its transfers,
+        /// which are then paid in the asset; `None` falls back to the `FeeRateOracle`.
+        #[weight = 10]
+        pub fn set_asset_fee_rate(origin, asset_id: AssetIdOf<T>, rate: Option<FixedU128>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            match rate {
+                Some(rate) => AssetFeeRates::<T>::insert(asset_id, rate),
+                None => AssetFeeRates::<T>::remove(asset_id),
+            }
+            Self::deposit_event(Event::<T>::AssetFeeRateUpdated(asset_id, rate));
+        }
+
+        /// Correct the estimated balance of this parachain's account on the Relay Chain.
+        #[weight = 10]
+        pub fn set_relay_float(origin, amount: BalanceOf<T>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            RelayFloat::<T>::put(amount);
+            Self::deposit_event(Event::<T>::RelayFloatUpdated(amount));
+        }
+
+        /// Set the pallet and call indices used by `EncodedRelayCall`, e.g. after a Relay Chain
+        /// runtime upgrade changed them.
+        #[weight = 10]
+        pub fn set_relay_call_indices(origin, indices: RelayCallIndices) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            RelayIndices::put(indices);
+            Self::deposit_event(Event::<T>::RelayCallIndicesUpdated(indices));
+        }
+
+        /// Register the XCM `location` of a local asset, or Currency when `asset_id` is `None`,
+        /// replacing its previous location. Locations are given as seen from a sibling
+        /// parachain.
+        #[weight = 10]
+        pub fn register_asset_location(origin, asset_id: Option<AssetIdOf<T>>, location: MultiLocation) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                Self::location_asset(&location).map_or(true, |registered| registered == asset_id),
+                Error::<T>::LocationAlreadyRegistered
+            );
+            if let Some(previous) = AssetLocations::<T>::take(asset_id) {
+                LocationAssets::<T>::remove(previous);
+            }
+            AssetLocations::<T>::insert(asset_id, location.clone());
+            LocationAssets::<T>::insert(location.clone(), asset_id);
+            Self::deposit_event(Event::<T>::AssetLocationRegistered(asset_id, location));
+        }
+
+        /// Remove the XCM location of a local asset, or Currency when `asset_id` is `None`.
+        #[weight = 10]
+        pub fn deregister_asset_location(origin, asset_id: Option<AssetIdOf<T>>) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let location = AssetLocations::<T>::take(asset_id).ok_or(Error::<T>::AssetNotRegistered)?;
+            LocationAssets::<T>::remove(location);
+            Self::deposit_event(Event::<T>::AssetLocationDeregistered(asset_id));
+        }
+
+        /// Set how XCMP messages are sent to the parachain at `para_id`. `RelayRouted` sends them
+        /// through the Relay Chain when there is no direct channel to that parachain.
+        #[weight = 10]
+        pub fn set_xcmp_transport(origin, para_id: u32, transport: XCMPTransport) {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let para_id: ParaId = para_id.into();
+            XCMPTransports::insert(para_id, transport);
+            Self::deposit_event(Event::<T>::XCMPTransportUpdated(para_id, transport));
+        }
+
+        fn deposit_event() = default;
+
+        /// Execute the delayed transfers and release the quarantined credits due in block `n`.
+        fn on_initialize(n: T::BlockNumber) -> Weight {
+            let ids = TransferQueue::<T>::take(n);
+            let weight = T::DbWeight::get().reads_writes(1, 1).saturating_add(
+                (ids.len() as Weight).saturating_mul(T::DelayedTransferWeight::get()),
+            );
+            for id in ids.iter() {
+                if let Some(transfer) = PendingTransfers::<T>::take(id) {
+                    let res = Self::execute_pending_transfer(transfer);
+                    Self::deposit_event(Event::<T>::DelayedTransferExecuted(*id, res));
+                }
+            }
+            let quarantine_ids = QuarantineQueue::<T>::take(n);
+            for id in quarantine_ids.iter() {
+                if let Some(credit) = Quarantine::<T>::take(id) {
+                    Self::release_credit(*id, credit);
+                }
+            }
+            weight
+                .saturating_add(T::DbWeight::get().reads_writes(1, 1))
+                .saturating_add(
+                    (quarantine_ids.len() as Weight)
+                        .saturating_mul(T::QuarantineReleaseWeight::get()),
+                )
+        }
+    }
+}
+
+/// This is a hack to convert from one generic type to another where we are sure that both are the
+/// same type/use the same encoding.
+fn convert_hack<O: Decode>(input: &impl Encode) -> O {
+    input.using_encoded(|e| Decode::decode(&mut &e[..]).expect("Must be compatible; qed"))
+}
+
+impl<T: Trait> Module<T> {
+    /// Transfer Asset(asset_id == Some(id)) or Currency (asset_id == None) to the Relay Chain;
+    /// This transfers Asset/Currency from this Parachain's account to the RelayAccount on this
+    /// parachain and sends an upward message to the relay chain
+    ///
+    /// Fails unless `amount` is covered by the `Backing` received from the Relay Chain and by
+    /// the `RelayFloat`, this does not guarentee that Relay Chain `dest` account is credited.
+    /// Transfers above the `DelayThresholds` of the asset are queued instead.
+    pub fn make_transfer_to_relay_chain(
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        Self::transfer_or_queue(Counterparty::Relay, asset_id, from, dest, amount, false)
+    }
+
+    /// Transfer Asset(asset_id == Some(id)) or Currency (asset_id == None) to a dest Parachain at
+    /// para_id;
+    /// This transfers Asset/Currency from this Parachain's account to the dest Parachain's
+    /// account, derived from their para_id, on this parachain and sends an XCM
+    /// `ReserveAssetDeposit`, or `WithdrawAsset` for assets dest parachain is the reserve of, to
+    /// dest parachain
+    /// INFO: The asset is identified to the other parachain by its location in `AssetLocations`.
+    /// INFO: The XCMP message is routed through the Relay Chain if the `XCMPTransport` of
+    /// `para_id` is `RelayRouted`.
+    ///
+    /// Fails unless `amount` is covered by the `Backing` received from the dest parachain, or
+    /// the asset is reserved on this parachain, this function does not guarentee that dest
+    /// parachain `dest` account is credited.
+    /// Transfers above the `DelayThresholds` of the asset are queued instead.
+    pub fn make_transfer_to_parachain(
+        from: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        para_id: ParaId,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        let counterparty = Counterparty::Parachain(para_id);
+        Self::transfer_or_queue(counterparty, asset_id, from, dest, amount, false)
+    }
+
+    /// The XCM transferring `amount` of the asset (or Currency when `asset_id` is `None`) into
+    /// the `dest` account on another parachain: a `ReserveAssetDeposit` when this parachain is
+    /// the reserve of the asset, a `WithdrawAsset` otherwise, as for the Relay Chain token and
+    /// assets reserved at the destination or on a third chain.
+    fn transfer_xcm(
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+        dest: &T::AccountId,
+    ) -> Result<VersionedXcm, Error<T>> {
+        let asset = Self::multi_asset(asset_id, amount)?;
+        let assets = vec![asset.clone()];
+        let effects = vec![Order::DepositAsset {
+            assets: vec![MultiAsset::All],
+            dest: Self::account_location(dest)?,
+        }];
+        match asset {
+            MultiAsset::ConcreteFungible { id, .. } if Self::is_self_reserved(&id) => {
+                Ok(VersionedXcm::V0(Xcm::ReserveAssetDeposit {
+                    assets,
+                    effects,
+                }))
+            }
+            _ => Ok(VersionedXcm::V0(Xcm::WithdrawAsset { assets, effects })),
+        }
+    }
+
+    /// Prepare `msg` to be sent to the parachain at `para_id` over its `XCMPTransport`, so it
+    /// is known to be deliverable before any funds are moved.
+    fn prepare_xcm(
+        para_id: ParaId,
+        msg: VersionedXcm,
+    ) -> Result<OutboundXcm<T::UpwardMessage>, Error<T>> {
+        match Self::xcmp_transport(para_id) {
+            XCMPTransport::Direct => Ok(OutboundXcm::Direct(para_id, msg)),
+            XCMPTransport::RelayRouted => {
+                <T::UpwardMessage as upward_messages::XCMPMessage>::send_message(
+                    para_id,
+                    msg.encode(),
+                )
+                .map(OutboundXcm::RelayRouted)
+                .ok_or(Error::<T>::UnsupportedRelayCall)
+            }
+        }
+    }
+
+    /// Send a message prepared by `prepare_xcm`.
+    fn send_xcm(msg: OutboundXcm<T::UpwardMessage>) {
+        match msg {
+            OutboundXcm::Direct(para_id, msg) => {
+                T::XCMPMessageSender::send_xcmp_message(para_id, &msg)
+                    .expect("Should not fail; qed");
+            }
+            OutboundXcm::RelayRouted(msg) => {
+                Self::send_upward_message(&msg, T::XCMPRoutingOrigin::get());
+            }
+        }
+    }
+
+    /// Transfer `amount` to `dest` on `counterparty`, or hold it in escrow until executed after
+    /// `TransferDelay` blocks if it is above the `DelayThresholds` of the asset.
+    fn transfer_or_queue(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+        keep_alive: bool,
+    ) -> DispatchResult {
+        match Self::delay_threshold(asset_id) {
+            Some(threshold) if amount > threshold => {}
+            _ => {
+                return Self::execute_transfer(
+                    counterparty,
+                    asset_id,
+                    from,
+                    dest,
+                    amount,
+                    keep_alive,
+                )
+            }
+        }
+
+        Self::prepare_transfer(counterparty, asset_id, from, dest, amount, keep_alive)?;
+        let delay = T::TransferDelay::get().max(One::one());
+        let execute_at = Self::queue_block(
+            <frame_system::Module<T>>::block_number().saturating_add(delay),
+            Self::last_transfer_block(),
+            Self::transfer_queue,
+            T::MaxDelayedTransfers::get(),
+        );
+        let escrow = Self::sovereign_account(counterparty, AccountPurpose::Escrow);
+        Self::transfer_funds(from, asset_id, &escrow, amount)?;
+        let id = Self::next_transfer_id();
+        NextTransferId::put(id.wrapping_add(1));
+        PendingTransfers::<T>::insert(
+            id,
+            PendingTransfer {
+                from: from.clone(),
+                counterparty,
+                asset_id: *asset_id,
+                dest: dest.clone(),
+                amount,
+                keep_alive,
+                execute_at,
+            },
+        );
+        TransferQueue::<T>::append(execute_at, id);
+        LastTransferBlock::<T>::put(execute_at);
+        Self::deposit_event(Event::<T>::TransferQueued(
+            id,
+            from.clone(),
+            counterparty,
+            *asset_id,
+            dest.clone(),
+            amount,
+            execute_at,
+        ));
+        Ok(())
+    }
+
+    /// Transfer `amount` to `dest` on `counterparty` and emit the transfer event: settle it on
+    /// this parachain, then send the upward message transferring it from this parachain's account
+    /// on the Relay Chain, or the XCM message to the parachain.
+    fn execute_transfer(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+        keep_alive: bool,
+    ) -> DispatchResult {
+        let msg = Self::prepare_transfer(counterparty, asset_id, from, dest, amount, keep_alive)?;
+        let fee = Self::settle_outbound_with_fee(counterparty, asset_id, from, amount)?;
+        match msg {
+            OutboundTransfer::Relay(msg) => {
+                // The float is drawn down once the transfer is settled, so it is left untouched
+                // if the settlement fails
+                Self::withdraw_relay_float(amount)?;
+                Self::send_upward_message(&msg, T::RelayTransferOrigin::get());
+                Self::deposit_event(Event::<T>::TransferredTokensToRelayChain(
+                    from.clone(),
+                    *asset_id,
+                    dest.clone(),
+                    amount,
+                    fee,
+                ));
+            }
+            OutboundTransfer::Parachain(para_id, msg) => {
+                Self::send_xcm(msg);
+                Self::deposit_event(Event::<T>::TransferredTokensToParachain(
+                    from.clone(),
+                    *asset_id,
+                    para_id,
+                    dest.clone(),
+                    amount,
+                    fee,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the outbound transfer against everything it is executed with, without moving any
+    /// funds, and prepare the message executing it. Delayed transfers are checked with it when
+    /// queued, so transfers which cannot be executed are not delayed.
+    fn prepare_transfer(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+        keep_alive: bool,
+    ) -> Result<OutboundTransfer<T::UpwardMessage>, DispatchError> {
+        Self::ensure_transfer_allowed(from, counterparty, dest, asset_id, amount)?;
+        let msg = match counterparty {
+            Counterparty::Relay => {
+                // Upward message to Relay Chain to transfer `amount` from this parachain's
+                // account on the relay chain to dest account.
+                let msg = if keep_alive {
+                    <T::UpwardMessage>::transfer_keep_alive(dest.clone(), amount)
+                } else {
+                    <T::UpwardMessage>::transfer(dest.clone(), amount)
+                }
+                .ok_or(Error::<T>::UnsupportedRelayCall)?;
+                ensure!(
+                    amount <= Self::relay_float(),
+                    Error::<T>::InsufficientRelayFloat
+                );
+                OutboundTransfer::Relay(msg)
+            }
+            Counterparty::Parachain(para_id) => {
+                ensure!(
+                    Self::is_permitted(para_id, Direction::Outbound),
+                    Error::<T>::ParachainNotPermitted
+                );
+                let msg = Self::transfer_xcm(asset_id, amount, dest)?;
+                OutboundTransfer::Parachain(para_id, Self::prepare_xcm(para_id, msg)?)
+            }
+        };
+        ensure!(
+            !Self::is_paused(counterparty, asset_id, Direction::Outbound),
+            Error::<T>::TransfersPaused
+        );
+        let fee = Self::fee_for(counterparty, asset_id, amount);
+        if let Some(fee) = Self::fee_in_asset(asset_id, fee) {
+            ensure!(
+                Self::balance_of(asset_id, from) >= amount.saturating_add(fee),
+                Error::<T>::InsufficientBalance
+            );
+        }
+        ensure!(
+            amount >= Self::minimum_transfer(counterparty, asset_id),
+            Error::<T>::BelowMinimumTransfer
+        );
+        ensure!(
+            !Self::draws_on_backing(counterparty, asset_id)
+                || amount <= Self::backing(counterparty, asset_id),
+            Error::<T>::InsufficientBacking
+        );
+        Self::ensure_credit_limit(counterparty, asset_id, amount, Zero::zero())?;
+        Self::ensure_outflow_rate(asset_id, amount)?;
+        Self::ensure_quota(from, asset_id, amount)?;
+        Ok(msg)
+    }
+
+    /// The block to queue an item due at `earliest` for, given the block `last` the latest item
+    /// was queued for: `earliest` or `last`, whichever is later, or the block after it once it
+    /// holds `max` items. No item was queued after `last`, so that block has room and full blocks
+    /// are skipped in one step.
+    fn queue_block(
+        earliest: T::BlockNumber,
+        last: T::BlockNumber,
+        queue: impl Fn(T::BlockNumber) -> Vec<u64>,
+        max: u32,
+    ) -> T::BlockNumber {
+        let block = earliest.max(last);
+        if queue(block).len() as u32 >= max.max(1) {
+            block.saturating_add(One::one())
+        } else {
+            block
+        }
+    }
+
+    /// Ensure the `TransferFilter` allows the outbound transfer.
+    fn ensure_transfer_allowed(
+        from: &T::AccountId,
+        counterparty: Counterparty,
+        dest: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        ensure!(
+            T::TransferFilter::allow_outbound(from, counterparty, dest, asset_id, amount),
+            Error::<T>::TransferFiltered
+        );
+        Ok(())
+    }
+
+    /// Return the escrowed funds of a delayed transfer to its sender and execute it, so it is
+    /// checked against the state of its execution block.
+    fn execute_pending_transfer(
+        transfer: PendingTransfer<T::AccountId, AssetIdOf<T>, BalanceOf<T>, T::BlockNumber>,
+    ) -> DispatchResult {
+        Self::release_escrow(&transfer)?;
+        Self::execute_transfer(
+            transfer.counterparty,
+            &transfer.asset_id,
+            &transfer.from,
+            &transfer.dest,
+            transfer.amount,
+            transfer.keep_alive,
+        )
+    }
+
+    /// Remove a delayed transfer from the queue, returning its funds to its sender.
+    fn unqueue_transfer(
+        id: u64,
+        transfer: PendingTransfer<T::AccountId, AssetIdOf<T>, BalanceOf<T>, T::BlockNumber>,
+    ) -> DispatchResult {
+        Self::release_escrow(&transfer)?;
+        PendingTransfers::<T>::remove(id);
+        TransferQueue::<T>::mutate(transfer.execute_at, |ids| {
+            ids.retain(|queued| *queued != id)
+        });
+        Ok(())
+    }
+
+    /// Move the funds of a delayed transfer from escrow back to its sender.
+    fn release_escrow(
+        transfer: &PendingTransfer<T::AccountId, AssetIdOf<T>, BalanceOf<T>, T::BlockNumber>,
+    ) -> DispatchResult {
+        let escrow = Self::sovereign_account(transfer.counterparty, AccountPurpose::Escrow);
+        match transfer.asset_id {
+            Some(id) => <assets::Module<T>>::make_transfer(
+                &escrow,
+                id,
+                &transfer.from,
+                convert_hack(&transfer.amount),
+            ),
+            None => T::Currency::transfer(
+                &escrow,
+                &transfer.from,
+                transfer.amount,
+                ExistenceRequirement::AllowDeath,
+            ),
+        }
+    }
+
+    /// The XCM `ConcreteFungible` for `amount` of a local asset, or Currency when `asset_id` is
+    /// `None`.
+    pub fn multi_asset(
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) -> Result<MultiAsset, Error<T>> {
+        let id = Self::asset_location(asset_id).ok_or(Error::<T>::AssetNotRegistered)?;
+        let amount = amount.try_into().map_err(|_| Error::<T>::AmountOverflow)?;
+        Ok(MultiAsset::ConcreteFungible { id, amount })
+    }
+
+    /// The local asset, or Currency when `None`, and amount of an XCM `ConcreteFungible`.
+    pub fn local_asset(
+        asset: &MultiAsset,
+    ) -> Result<(Option<AssetIdOf<T>>, BalanceOf<T>), Error<T>> {
+        match asset {
+            MultiAsset::ConcreteFungible { id, amount } => {
+                let asset_id = Self::location_asset(id).ok_or(Error::<T>::AssetNotRegistered)?;
+                let amount =
+                    BalanceOf::<T>::try_from(*amount).map_err(|_| Error::<T>::AmountOverflow)?;
+                Ok((asset_id, amount))
+            }
+            _ => Err(Error::<T>::AssetNotRegistered),
+        }
+    }
+
+    /// The sibling parachain holding the reserve of the asset at `location`, i.e. the one
+    /// `location` starts with.
+    fn reserve_of(location: &MultiLocation) -> Option<ParaId> {
+        match location {
+            MultiLocation::X2(Junction::Parent, Junction::Parachain { id })
+            | MultiLocation::X3(Junction::Parent, Junction::Parachain { id }, _)
+            | MultiLocation::X4(Junction::Parent, Junction::Parachain { id }, _, _) => {
+                Some((*id).into())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this parachain holds the reserve of the asset at `location`.
+    fn is_self_reserved(location: &MultiLocation) -> bool {
+        Self::reserve_of(location) == Some(T::SelfParaId::get())
+    }
+
+    /// The XCM location of `account`, which must be a 32 bytes account id.
+    fn account_location(account: &T::AccountId) -> Result<MultiLocation, Error<T>> {
+        let encoded = account.encode();
+        ensure!(encoded.len() == 32, Error::<T>::UnsupportedAccount);
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&encoded);
+        Ok(MultiLocation::X1(Junction::AccountId32 {
+            network: NetworkId::Any,
+            id,
+        }))
+    }
+
+    /// The local account of an XCM `AccountId32` location.
+    fn location_account(location: &MultiLocation) -> Option<T::AccountId> {
+        match location {
+            MultiLocation::X1(Junction::AccountId32 { id, .. }) => {
+                T::AccountId::decode(&mut &id[..]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Pay out the `assets` received from the parachain `src` to the accounts of the
+    /// `DepositAsset` orders in `effects`, each taking the assets matched by its filter. With
+    /// `reserve_deposit` the assets must all be reserved at `src`, otherwise none may be.
+    /// `None`, without paying out anything, if an asset, reserve or account is not understood or
+    /// if any assets are left unmatched.
+    fn deposit_xcm_assets(
+        src: ParaId,
+        assets: &[MultiAsset],
+        effects: &[Order],
+        reserve_deposit: bool,
+    ) -> Option<()> {
+        let mut holding = assets
+            .iter()
+            .map(|asset| match asset {
+                MultiAsset::ConcreteFungible { id, .. }
+                    if (Self::reserve_of(id) == Some(src)) == reserve_deposit =>
+                {
+                    let (asset_id, amount) = Self::local_asset(asset).ok()?;
+                    Some((id, asset_id, amount))
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut deposits = Vec::new();
+        for order in effects {
+            if let Order::DepositAsset {
+                assets: filter,
+                dest,
+            } = order
+            {
+                let dest = Self::location_account(dest)?;
+                for wildcard in filter {
+                    for (location, asset_id, amount) in holding.iter_mut() {
+                        let taken = match wildcard {
+                            MultiAsset::All | MultiAsset::AllFungible => *amount,
+                            MultiAsset::AllConcreteFungible { id } if id == *location => *amount,
+                            MultiAsset::ConcreteFungible { id, amount: limit }
+                                if id == *location =>
+                            {
+                                BalanceOf::<T>::try_from(*limit)
+                                    .map_or(*amount, |limit| limit.min(*amount))
+                            }
+                            _ => Zero::zero(),
+                        };
+                        if !taken.is_zero() {
+                            *amount = amount.saturating_sub(taken);
+                            deposits.push((dest.clone(), asset_id.clone(), taken));
+                        }
+                    }
+                }
+            }
+        }
+        if holding.iter().any(|(_, _, amount)| !amount.is_zero()) {
+            return None;
+        }
+
+        for (dest, asset_id, amount) in deposits {
+            let res = Self::settle_inbound(Counterparty::Parachain(src), &asset_id, &dest, amount);
+            Self::deposit_event(Event::<T>::TransferredTokensViaXCMP(
+                src, dest, amount, asset_id, res,
+            ));
+        }
+        Some(())
+    }
+
+    /// Credit `amount` of the asset (or Currency when `asset_id` is `None`) to `dest` from the
+    /// RelayAccount, on request of the Relay Chain.
+    fn credit_from_relay_chain(
+        dest: T::AccountId,
+        asset_id: Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) {
+        let res = Self::settle_inbound(Counterparty::Relay, &asset_id, &dest, amount);
+        Self::deposit_event(Event::<T>::TransferredTokensFromRelayChain(
+            dest, amount, asset_id, res,
+        ));
+    }
+
+    /// Deduct `amount` transferred out of this parachain's account on the Relay Chain from its
+    /// estimated balance.
+    fn withdraw_relay_float(amount: BalanceOf<T>) -> DispatchResult {
+        let float = Self::relay_float();
+        ensure!(amount <= float, Error::<T>::InsufficientRelayFloat);
+        RelayFloat::<T>::put(float - amount);
+        Ok(())
+    }
+
+    /// The account holding, on this parachain, the funds for `purpose` of `counterparty`.
+    pub fn sovereign_account(counterparty: Counterparty, purpose: AccountPurpose) -> T::AccountId {
+        T::SovereignAccountOf::sovereign_account(counterparty, purpose)
+    }
+
+    /// Move `amount` of the asset (or Currency when `asset_id` is `None`) sent to `counterparty`
+    /// from `from` to the account of `counterparty`, once checked by `prepare_transfer`.
+    fn settle_outbound(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        let sovereign_account = Self::sovereign_account(counterparty, AccountPurpose::Transfers);
+        Self::transfer_funds(from, asset_id, &sovereign_account, amount)?;
+        if Self::draws_on_backing(counterparty, asset_id) {
+            Backing::<T>::mutate(counterparty, asset_id, |backing| {
+                *backing = backing.saturating_sub(amount)
+            });
+        }
+        TotalOutflow::<T>::mutate(counterparty, asset_id, |outflow| {
+            *outflow = outflow.saturating_add(amount)
+        });
+        Self::note_credit_exposure(counterparty, asset_id);
+        Self::note_outflow(asset_id, amount);
+        Self::note_account_outflow(from, asset_id, amount);
+        Ok(())
+    }
+
+    /// Whether transfers of the asset to `counterparty` draw on the `Backing` received from it:
+    /// transfers to the Relay Chain and `WithdrawAsset` transfers to other parachains do, reserve
+    /// transfers of assets reserved on this parachain do not.
+    fn draws_on_backing(counterparty: Counterparty, asset_id: &Option<AssetIdOf<T>>) -> bool {
+        match counterparty {
+            Counterparty::Relay => true,
+            Counterparty::Parachain(_) => !Self::asset_location(asset_id)
+                .map_or(false, |location| Self::is_self_reserved(&location)),
+        }
+    }
+
+    /// Ensure `who` transferring `amount` of the asset out keeps it within the quota of its
+    /// tier.
+    fn ensure_quota(
+        who: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        if let Some(quota) = Self::tier_quota(Self::account_tier(who), asset_id) {
+            let outflow = Self::account_outflow(who, asset_id, &quota).saturating_add(amount);
+            ensure!(outflow <= quota.amount, Error::<T>::QuotaExceeded);
+        }
+        Ok(())
+    }
+
+    /// The amount of the asset transferred out by `who` in the current period of `quota`.
+    fn account_outflow(
+        who: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        quota: &Quota<BalanceOf<T>, T::BlockNumber>,
+    ) -> BalanceOf<T> {
+        let (start, amount) = Self::account_outflows(who, asset_id);
+        if start == Self::period_start(quota.period) {
+            amount
+        } else {
+            Zero::zero()
+        }
+    }
+
+    /// The first block of the current period of `period` blocks.
+    fn period_start(period: T::BlockNumber) -> T::BlockNumber {
+        let now = <frame_system::Module<T>>::block_number();
+        if period.is_zero() {
+            now
+        } else {
+            now - now % period
+        }
+    }
+
+    /// Record `amount` of the asset transferred out by `who` if its tier has a quota of it.
+    fn note_account_outflow(
+        who: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) {
+        if let Some(quota) = Self::tier_quota(Self::account_tier(who), asset_id) {
+            let outflow = Self::account_outflow(who, asset_id, &quota).saturating_add(amount);
+            AccountOutflows::<T>::insert(
+                who,
+                asset_id,
+                (Self::period_start(quota.period), outflow),
+            );
+        }
+    }
+
+    /// Ensure transferring `amount` of the asset out keeps it within its `OutflowLimits`.
+    fn ensure_outflow_rate(
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        if let Some(limit) = Self::outflow_limit(asset_id) {
+            let outflow = Self::outflow_within(asset_id, &limit).saturating_add(amount);
+            ensure!(
+                outflow <= limit.amount,
+                Error::<T>::OutflowRateLimitExceeded
+            );
+        }
+        Ok(())
+    }
+
+    /// The amount of the asset transferred out in the buckets overlapping the last
+    /// `limit.window` blocks, so at least the amount transferred out within them.
+    fn outflow_within(
+        asset_id: &Option<AssetIdOf<T>>,
+        limit: &RateLimit<BalanceOf<T>, T::BlockNumber>,
+    ) -> BalanceOf<T> {
+        let bucket = Self::outflow_bucket(limit.window);
+        Self::recent_outflows(asset_id)
+            .iter()
+            .filter(|(start, _)| Self::overlaps_window(*start, bucket, limit.window))
+            .fold(Zero::zero(), |total, (_, amount)| {
+                total.saturating_add(*amount)
+            })
+    }
+
+    /// Record `amount` of a rate limited asset transferred out in the bucket of the current
+    /// block, dropping the buckets out of its window.
+    fn note_outflow(asset_id: &Option<AssetIdOf<T>>, amount: BalanceOf<T>) {
+        if let Some(limit) = Self::outflow_limit(asset_id) {
+            let bucket = Self::outflow_bucket(limit.window);
+            let now = <frame_system::Module<T>>::block_number();
+            let current = now - now % bucket;
+            RecentOutflows::<T>::mutate(asset_id, |outflows| {
+                outflows.retain(|(start, _)| Self::overlaps_window(*start, bucket, limit.window));
+                match outflows.last_mut() {
+                    Some((start, total)) if *start == current => {
+                        *total = total.saturating_add(amount)
+                    }
+                    _ => outflows.push((current, amount)),
+                }
+            });
+        }
+    }
+
+    /// Number of blocks of the buckets a window of `window` blocks is tracked in, so it
+    /// overlaps at most `OUTFLOW_BUCKETS + 1` of them.
+    fn outflow_bucket(window: T::BlockNumber) -> T::BlockNumber {
+        let buckets = T::BlockNumber::from(OUTFLOW_BUCKETS);
+        (window.saturating_add(buckets - One::one()) / buckets).max(One::one())
+    }
+
+    /// Whether the bucket of `bucket` blocks starting at `start` overlaps the last `window`
+    /// blocks.
+    fn overlaps_window(
+        start: T::BlockNumber,
+        bucket: T::BlockNumber,
+        window: T::BlockNumber,
+    ) -> bool {
+        let now = <frame_system::Module<T>>::block_number();
+        start.saturating_add(bucket).saturating_add(window) > now.saturating_add(One::one())
+    }
+
+    /// Withdraw the `TransferFee` of `amount` from `from` and settle the outbound transfer,
+    /// passing the fee to `FeeHandler` once settled or refunding it otherwise. Fees paid in the
+    /// asset are moved to the account of the `AssetFeeHandler` instead, and moved back if the
+    /// transfer is not settled.
+    fn settle_outbound_with_fee(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        from: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> Result<BalanceOf<T>, DispatchError> {
+        let fee = Self::fee_for(counterparty, asset_id, amount);
+        if let Some(fee) = Self::fee_in_asset(asset_id, fee) {
+            let fee_account = Self::charge_asset_fee(counterparty, asset_id, from, fee)?;
+            if let Err(e) = Self::settle_outbound(counterparty, asset_id, from, amount) {
+                if let Some(fee_account) = fee_account {
+                    let _ = Self::transfer_funds(&fee_account, asset_id, from, fee);
+                }
+                return Err(e);
+            }
+            return Ok(fee);
+        }
+
+        let imbalance = T::Currency::withdraw(
+            from,
+            fee,
+            WithdrawReason::Fee.into(),
+            ExistenceRequirement::KeepAlive,
+        )?;
+        if let Err(e) = Self::settle_outbound(counterparty, asset_id, from, amount) {
+            let _ = T::Currency::resolve_into_existing(from, imbalance);
+            return Err(e);
+        }
+        T::FeeHandler::on_unbalanced(imbalance);
+        Ok(fee)
+    }
+
+    /// Move `fee` in the asset from `who` to the account of the `AssetFeeHandler` for
+    /// `counterparty`, returned unless `fee` is zero or the asset is Currency.
+    fn charge_asset_fee(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        who: &T::AccountId,
+        fee: BalanceOf<T>,
+    ) -> Result<Option<T::AccountId>, DispatchError> {
+        match asset_id {
+            Some(id) if !fee.is_zero() => {
+                let fee_account = T::AssetFeeHandler::fee_account(counterparty, id);
+                Self::transfer_funds(who, asset_id, &fee_account, fee)?;
+                Ok(Some(fee_account))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The fee, in Currency, of transferring `amount` of the asset (or Currency when
+    /// `asset_id` is `None`) to `counterparty`.
+    pub fn fee_for(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        amount: BalanceOf<T>,
+    ) -> BalanceOf<T> {
+        Self::transfer_fee(counterparty, asset_id)
+            .map_or_else(Zero::zero, |fee| fee.flat.saturating_add(fee.rate * amount))
+    }
+
+    /// Units of the asset paid per unit of Currency for fees of its transfers, set by
+    /// governance or given by the `FeeRateOracle`.
+    pub fn fee_rate(asset_id: &AssetIdOf<T>) -> Option<FixedU128> {
+        Self::asset_fee_rate(asset_id).or_else(|| T::FeeRateOracle::fee_rate(asset_id))
+    }
+
+    /// `fee` in Currency converted to the asset, `None` unless fees of the asset are paid in it.
+    pub fn fee_in_asset(
+        asset_id: &Option<AssetIdOf<T>>,
+        fee: BalanceOf<T>,
+    ) -> Option<BalanceOf<T>> {
+        let rate = Self::fee_rate(asset_id.as_ref()?)?;
+        Some(
+            rate.saturating_mul_int(fee.saturated_into::<u128>())
+                .saturated_into(),
+        )
+    }
+
+    /// Whether transfers with the parachain at `para_id` are permitted in `direction` by the
+    /// allowlist and denylist.
+    pub fn is_permitted(para_id: ParaId, direction: Direction) -> bool {
+        match Self::parachain_access(para_id, direction) {
+            Some(Access::Allowed) => true,
+            Some(Access::Denied) => false,
+            None => !Self::allowlist_required(direction),
+        }
+    }
+
+    /// Ensure `origin` is either the `AdminOrigin` or the `PauseOrigin`.
+    fn ensure_pause_origin(origin: T::Origin) -> DispatchResult {
+        match T::AdminOrigin::try_origin(origin) {
+            Ok(_) => Ok(()),
+            Err(origin) => T::PauseOrigin::ensure_origin(origin)
+                .map(|_| ())
+                .map_err(Into::into),
+        }
+    }
+
+    /// Whether transfers of the asset (or Currency when `asset_id` is `None`) with
+    /// `counterparty` are paused in `direction`, globally, for the counterparty or for the asset.
+    pub fn is_paused(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        direction: Direction,
+    ) -> bool {
+        Self::paused(PauseScope::All, direction)
+            || Self::paused(PauseScope::Counterparty(counterparty), direction)
+            || Self::paused(PauseScope::Asset(*asset_id), direction)
+    }
+
+    /// Credit `amount` of the asset (or Currency when `asset_id` is `None`) received from
+    /// `counterparty` to `dest`, or quarantine it if it is above the `QuarantineThresholds`.
+    fn settle_inbound(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        match Self::quarantine_threshold(asset_id) {
+            Some(threshold) if amount > threshold => {
+                Self::quarantine_credit(counterparty, asset_id, dest, amount)
+            }
+            _ => Self::credit_inbound(counterparty, asset_id, dest, amount)?,
+        }
+        Backing::<T>::mutate(counterparty, asset_id, |backing| {
+            *backing = backing.saturating_add(amount)
+        });
+        Self::note_credit_exposure(counterparty, asset_id);
+        Ok(())
+    }
+
+    /// Hold `amount` received from `counterparty` in its account until released to `dest`
+    /// after `QuarantinePeriod` blocks, or in the next block with room for it once
+    /// `MaxQuarantineReleases` are released in that block, after any credit quarantined before it.
+    fn quarantine_credit(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) {
+        let id = Self::next_quarantine_id();
+        NextQuarantineId::put(id.wrapping_add(1));
+        let period = T::QuarantinePeriod::get().max(One::one());
+        let release_at = Self::queue_block(
+            <frame_system::Module<T>>::block_number().saturating_add(period),
+            Self::last_quarantine_block(),
+            Self::quarantine_queue,
+            T::MaxQuarantineReleases::get(),
+        );
+        Quarantine::<T>::insert(
+            id,
+            QuarantinedCredit {
+                counterparty,
+                dest: dest.clone(),
+                asset_id: *asset_id,
+                amount,
+                release_at,
+            },
+        );
+        QuarantineQueue::<T>::append(release_at, id);
+        LastQuarantineBlock::<T>::put(release_at);
+        Self::deposit_event(Event::<T>::CreditQuarantined(
+            id,
+            dest.clone(),
+            counterparty,
+            *asset_id,
+            amount,
+            release_at,
+        ));
+    }
+
+    /// Remove a quarantined credit from the quarantine.
+    fn unquarantine(
+        id: u64,
+        credit: &QuarantinedCredit<T::AccountId, AssetIdOf<T>, BalanceOf<T>, T::BlockNumber>,
+    ) {
+        Quarantine::<T>::remove(id);
+        QuarantineQueue::<T>::mutate(credit.release_at, |ids| ids.retain(|queued| *queued != id));
+    }
+
+    /// Credit a quarantined credit to its beneficiary, parking it for them to `claim` if that
+    /// fails.
+    fn release_credit(
+        id: u64,
+        credit: QuarantinedCredit<T::AccountId, AssetIdOf<T>, BalanceOf<T>, T::BlockNumber>,
+    ) {
+        let QuarantinedCredit {
+            counterparty,
+            dest,
+            asset_id,
+            amount,
+            ..
+        } = credit;
+        let res = Self::credit_inbound(counterparty, &asset_id, &dest, amount);
+        if res.is_err() {
+            Self::park_credit(counterparty, &asset_id, &dest, amount);
+        }
+        Self::note_credit_exposure(counterparty, &asset_id);
+        Self::deposit_event(Event::<T>::QuarantineReleased(id, res));
+    }
+
+    /// Credit `amount` of the asset (or Currency when `asset_id` is `None`) received from
+    /// `counterparty` to `dest`, or park it as a claim of `dest` while inbound transfers are
+    /// paused, if it is below the `MinimumTransfers`, if the `TransferFilter` rejects it or if it
+    /// would take the asset over the credit limit of `counterparty`.
+    fn credit_inbound(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        if Self::is_paused(counterparty, asset_id, Direction::Inbound)
+            || amount < Self::minimum_transfer(counterparty, asset_id)
+            || !T::TransferFilter::allow_inbound(counterparty, dest, asset_id, amount)
+            || Self::ensure_credit_limit(counterparty, asset_id, Zero::zero(), amount).is_err()
+        {
+            Self::park_credit(counterparty, asset_id, dest, amount);
+        } else {
+            Self::credit(counterparty, asset_id, dest, amount)?;
+        }
+        Ok(())
+    }
+
+    /// Keep `amount` received from `counterparty` in its account for `dest` to `claim`.
+    fn park_credit(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) {
+        let key = (counterparty, *asset_id);
+        let total = Self::claim_of(dest, key).saturating_add(amount);
+        Claims::<T>::insert(dest, key, total);
+        Self::deposit_event(Event::<T>::CreditParked(
+            dest.clone(),
+            counterparty,
+            *asset_id,
+            total,
+        ));
+    }
+
+    /// Pay out `amount` received from `counterparty` to `dest`, applying the `DustPolicy` if
+    /// `dest` has no balance of the asset and `amount`, with its pending credits, is below the
+    /// minimum balance.
+    fn credit(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        let key = (counterparty, *asset_id);
+        let total = Self::pending_credit(dest, key).saturating_add(amount);
+        match Self::credit_destination(asset_id, dest, total) {
+            Some(account) => {
+                Self::pay_out(counterparty, asset_id, &account, total)?;
+                PendingCredits::<T>::remove(dest, key);
+                if account != *dest {
+                    Self::deposit_event(Event::<T>::DustRouted(
+                        dest.clone(),
+                        *asset_id,
+                        total,
+                        account,
+                    ));
+                }
+            }
+            None => {
+                PendingCredits::<T>::insert(dest, key, total);
+                Self::deposit_event(Event::<T>::CreditPending(
+                    dest.clone(),
+                    counterparty,
+                    *asset_id,
+                    total,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The account `total` credited to `dest` can be paid out to: `dest` if it holds the asset
+    /// or `total` is at least the minimum balance, else the `DustPolicy` account if it routes
+    /// dust to an account holding the asset, else `None` as it has to stay pending.
+    fn credit_destination(
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        total: BalanceOf<T>,
+    ) -> Option<T::AccountId> {
+        if total >= Self::minimum_balance(asset_id) || !Self::balance_of(asset_id, dest).is_zero() {
+            return Some(dest.clone());
+        }
+        match T::DustPolicy::get() {
+            DustPolicy::RouteTo(dust_account)
+                if !Self::balance_of(asset_id, &dust_account).is_zero() =>
+            {
+                Some(dust_account)
+            }
+            _ => None,
+        }
+    }
+
+    /// Transfer `amount` of the asset (or Currency when `asset_id` is `None`) from the account
+    /// of `counterparty` to `dest`.
+    fn pay_out(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        let sovereign_account = Self::sovereign_account(counterparty, AccountPurpose::Transfers);
+        Self::transfer_funds(&sovereign_account, asset_id, dest, amount)?;
+        TotalInflow::<T>::mutate(counterparty, asset_id, |inflow| {
+            *inflow = inflow.saturating_add(amount)
+        });
+        Ok(())
+    }
+
+    /// The minimum balance of an account holding the asset, or Currency when `asset_id` is
+    /// `None`.
+    fn minimum_balance(asset_id: &Option<AssetIdOf<T>>) -> BalanceOf<T> {
+        match asset_id {
+            Some(_) => T::AssetMinimumBalance::get(),
+            None => T::Currency::minimum_balance(),
+        }
+    }
+
+    /// The difference between the amounts of the asset sent to and received from `counterparty`,
+    /// after sending `outflow` and receiving `inflow` more.
+    fn credit_exposure(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        outflow: BalanceOf<T>,
+        inflow: BalanceOf<T>,
+    ) -> BalanceOf<T> {
+        let outflow = Self::total_outflow(counterparty, asset_id).saturating_add(outflow);
+        let inflow = Self::total_inflow(counterparty, asset_id).saturating_add(inflow);
+        if outflow > inflow {
+            outflow - inflow
+        } else {
+            inflow - outflow
+        }
+    }
+
+    /// Ensure sending `outflow` to and receiving `inflow` from `counterparty` keeps the asset
+    /// within the credit limit of the parachain, or at least does not increase an exposure
+    /// already above it, e.g. after the limit was lowered.
+    fn ensure_credit_limit(
+        counterparty: Counterparty,
+        asset_id: &Option<AssetIdOf<T>>,
+        outflow: BalanceOf<T>,
+        inflow: BalanceOf<T>,
+    ) -> DispatchResult {
+        if let Counterparty::Parachain(para_id) = counterparty {
+            if let Some(limit) = Self::credit_limit(para_id, asset_id) {
+                let exposure = Self::credit_exposure(counterparty, asset_id, outflow, inflow);
+                let current =
+                    Self::credit_exposure(counterparty, asset_id, Zero::zero(), Zero::zero());
+                ensure!(
+                    exposure <= limit || exposure <= current,
+                    Error::<T>::CreditLimitExceeded
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit `CreditLimitApproached` if the asset is close to the credit limit of the parachain.
+    fn note_credit_exposure(counterparty: Counterparty, asset_id: &Option<AssetIdOf<T>>) {
+        if let Counterparty::Parachain(para_id) = counterparty {
+            if let Some(limit) = Self::credit_limit(para_id, asset_id) {
+                let exposure =
+                    Self::credit_exposure(counterparty, asset_id, Zero::zero(), Zero::zero());
+                if exposure >= T::CreditLimitWarning::get() * limit {
+                    Self::deposit_event(Event::<T>::CreditLimitApproached(
+                        para_id, *asset_id, exposure, limit,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// The flows with counterparties which do not match the balance of their accounts on this
+    /// parachain, i.e. `balance + inflow != opening + outflow`. Covers each asset with a
+    /// registered location, or any backing, flow or opening balance, for the Relay Chain and
+    /// each parachain with a transport, access, credit limit, backing, flow or opening balance.
+    pub fn reconciliation_discrepancies() -> Vec<Reconciliation<AssetIdOf<T>, BalanceOf<T>>> {
+        let mut counterparties = vec![Counterparty::Relay];
+        let mut assets: Vec<Option<AssetIdOf<T>>> = AssetLocations::<T>::iter()
+            .map(|(asset_id, _)| asset_id)
+            .collect();
+        let paras = XCMPTransports::iter()
+            .map(|(para_id, _)| para_id)
+            .chain(ParachainAccess::iter().map(|(para_id, _, _)| para_id))
+            .chain(CreditLimits::<T>::iter().map(|(para_id, _, _)| para_id));
+        let flows = Backing::<T>::iter()
+            .map(|(counterparty, asset_id, _)| (counterparty, asset_id))
+            .chain(
+                TotalInflow::<T>::iter()
+                    .map(|(counterparty, asset_id, _)| (counterparty, asset_id)),
+            )
+            .chain(
+                TotalOutflow::<T>::iter()
+                    .map(|(counterparty, asset_id, _)| (counterparty, asset_id)),
+            )
+            .chain(
+                OpeningBalances::<T>::iter()
+                    .map(|(counterparty, asset_id, _)| (counterparty, asset_id)),
+            );
+        for (counterparty, asset_id) in paras
+            .map(|para_id| (Counterparty::Parachain(para_id), None))
+            .chain(flows)
+        {
+            if !counterparties.contains(&counterparty) {
+                counterparties.push(counterparty);
+            }
+            if !assets.contains(&asset_id) {
+                assets.push(asset_id);
+            }
+        }
+
+        let mut discrepancies = Vec::new();
+        for counterparty in counterparties {
+            for asset_id in assets.iter() {
+                let report = Reconciliation {
+                    counterparty,
+                    asset_id: *asset_id,
+                    inflow: Self::total_inflow(counterparty, asset_id),
+                    outflow: Self::total_outflow(counterparty, asset_id),
+                    opening: Self::opening_balance(counterparty, asset_id),
+                    balance: Self::balance_of(
+                        asset_id,
+                        &Self::sovereign_account(counterparty, AccountPurpose::Transfers),
+                    ),
+                };
+                if report.balance.saturating_add(report.inflow)
+                    != report.opening.saturating_add(report.outflow)
+                {
+                    discrepancies.push(report);
+                }
+            }
+        }
+        discrepancies
+    }
+
+    /// The balance of `who` in the asset, or Currency when `asset_id` is `None`.
+    fn balance_of(asset_id: &Option<AssetIdOf<T>>, who: &T::AccountId) -> BalanceOf<T> {
+        match asset_id {
+            Some(id) => convert_hack(&<assets::Module<T>>::balance(*id, who.clone())),
+            None => T::Currency::total_balance(who),
+        }
+    }
+
+    /// Transfer `amount` of the asset (or Currency when `asset_id` is `None`) from the
+    /// `source` account to `dest`.
+    fn transfer_funds(
+        source: &T::AccountId,
+        asset_id: &Option<AssetIdOf<T>>,
+        dest: &T::AccountId,
+        amount: BalanceOf<T>,
+    ) -> DispatchResult {
+        match asset_id {
+            Some(id) => {
+                <assets::Module<T>>::make_transfer(source, *id, dest, convert_hack(&amount))
+            }
+            None => T::Currency::transfer(source, dest, amount, ExistenceRequirement::KeepAlive),
+        }
+    }
+
+    /// Send `msg` to the Relay Chain, to be dispatched there with `origin`.
+    fn send_upward_message(msg: &T::UpwardMessage, origin: UpwardMessageOrigin) {
+        T::UpwardMessageSender::send_upward_message(msg, origin).expect("Should not fail; qed");
+    }
+}
+
+impl<T: Trait> DownwardMessageHandler for Module<T> {
+    /// Handles messages from the Relay Chain, matches `TransferInto` and `Opaque` messages
+    /// carrying exactly a `VersionedDownwardPayload`; an event is emitted for any other message,
+    /// including payloads with trailing bytes.
+    /// Here we use the remark field of the downward message to decode into Option<AssetId>
+    fn handle_downward_message(msg: &DownwardMessage) {
+        match msg {
+            DownwardMessage::TransferInto(dest, relay_amount, remark) => {
+                let dest: T::AccountId = convert_hack(&dest);
+                let relay_amount: BalanceOf<T> = convert_hack(relay_amount);
+                // remark has a concerte type [u8; 32]
+                let asset_id: Option<AssetIdOf<T>> = convert_hack(remark);
+                RelayFloat::<T>::mutate(|float| *float = float.saturating_add(relay_amount));
+                Self::credit_from_relay_chain(dest, asset_id, relay_amount);
+            }
+            DownwardMessage::Opaque(payload) => {
+                match VersionedDownwardPayload::<T::AccountId, BalanceOf<T>, AssetIdOf<T>>::decode_all(
+                    &payload[..],
+                ) {
+                    Ok(VersionedDownwardPayload::V0(DownwardPayload::Credit(credits))) => {
+                        for (dest, asset_id, amount) in credits {
+                            Self::credit_from_relay_chain(dest, asset_id, amount);
+                        }
+                    }
+                    Err(_) => Self::deposit_event(Event::<T>::UnrecognisedDownwardMessage(
+                        T::Hashing::hash_of(msg),
+                    )),
+                }
+            }
+            _ => Self::deposit_event(Event::<T>::UnrecognisedDownwardMessage(
+                T::Hashing::hash_of(msg),
+            )),
+        }
+    }
+}
+
+impl<T: Trait> XCMPMessageHandler<VersionedXcm> for Module<T> {
+    /// Handles XCM messages from other parachains, only match to `WithdrawAsset` of assets the
+    /// sender is not the reserve of and `ReserveAssetDeposit` of assets it is the reserve of,
+    /// both paid out from the parachain's account on this parachain to the accounts of their
+    /// `DepositAsset` orders. Messages from parachains inbound transfers are not permitted with
+    /// are ignored.
+    fn handle_xcmp_message(src: ParaId, msg: &VersionedXcm) {
+        if !Self::is_permitted(src, Direction::Inbound) {
+            Self::deposit_event(Event::<T>::XCMPMessageRejected(
+                src,
+                T::Hashing::hash_of(msg),
+            ));
+            return;
+        }
+
+        let deposited = match msg {
+            VersionedXcm::V0(Xcm::WithdrawAsset { assets, effects }) => {
+                Self::deposit_xcm_assets(src, assets, effects, false)
+            }
+            VersionedXcm::V0(Xcm::ReserveAssetDeposit { assets, effects }) => {
+                Self::deposit_xcm_assets(src, assets, effects, true)
+            }
+            _ => None,
+        };
+
+        if deposited.is_none() {
+            Self::deposit_event(Event::<T>::UnrecognisedXCMPMessage(
+                src,
+                T::Hashing::hash_of(msg),
+            ));
         }
     }
 }
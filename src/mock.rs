@@ -22,7 +22,11 @@
 
 pub use super::*;
 use cumulus_message_broker;
-use frame_support::{impl_outer_event, impl_outer_origin, parameter_types, weights::Weight};
+use frame_support::{
+    impl_outer_dispatch, impl_outer_event, impl_outer_origin, parameter_types,
+    traits::Contains,
+    weights::{IdentityFee, Weight},
+};
 use polkadot_core_primitives::AccountId as AccountId32;
 use sp_core::H256;
 use sp_io;
@@ -31,6 +35,7 @@ use sp_runtime::{
     traits::{BlakeTwo256, IdentityLookup},
     Perbill,
 };
+use std::cell::RefCell;
 impl_outer_origin! {
     pub enum Origin for Test where system = frame_system {}
 }
@@ -45,10 +50,21 @@ type AssetId = u32;
 pub struct Test;
 parameter_types! {
     pub const ExistentialDeposit: Balance = 100;
+    pub const RelayExistentialDeposit: Balance = 10;
     pub const BlockHashCount: u64 = 250;
     pub const MaximumBlockWeight: Weight = 1024;
     pub const MaximumBlockLength: u32 = 2 * 1024;
     pub const AvailableBlockRatio: Perbill = Perbill::one();
+    pub const RelayTransferOrigin: UpwardMessageOrigin = UpwardMessageOrigin::Signed;
+    pub const XCMPRoutingOrigin: UpwardMessageOrigin = UpwardMessageOrigin::Signed;
+    pub const CreditLimitWarning: Perbill = Perbill::from_percent(80);
+    pub const AssetMinimumBalance: Balance = 10;
+    pub const MaxDelayedTransfers: u32 = 2;
+    pub const DelayedTransferWeight: Weight = 100;
+    pub const MaxQuarantineReleases: u32 = 2;
+    pub const QuarantineReleaseWeight: Weight = 100;
+    pub const TransactionByteFee: Balance = 1;
+    pub SelfParaId: ParaId = 100.into();
 }
 
 impl frame_system::Trait for Test {
@@ -56,7 +72,7 @@ impl frame_system::Trait for Test {
     type Origin = Origin;
     type Index = u64;
     type BlockNumber = u64;
-    type Call = ();
+    type Call = TestCall;
     type Hash = H256;
     type Hashing = BlakeTwo256;
     type AccountId = AccountId;
@@ -85,35 +101,110 @@ impl assets::Trait for Test {
     type AssetId = AssetId;
 }
 
-#[derive(Encode, Decode)]
-pub struct TestUpwardMessage {}
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub enum TestUpwardMessage {
+    Transfer(AccountId, Balance),
+    TransferKeepAlive(AccountId, Balance),
+    TransferAll(AccountId, bool),
+    SendMessage(ParaId, Vec<u8>),
+}
 impl upward_messages::BalancesMessage<AccountId, Balance> for TestUpwardMessage {
-    fn transfer(_a: AccountId, _b: Balance) -> Self {
-        TestUpwardMessage {}
+    fn transfer(a: AccountId, b: Balance) -> Option<Self> {
+        Some(TestUpwardMessage::Transfer(a, b))
+    }
+
+    fn transfer_keep_alive(a: AccountId, b: Balance) -> Option<Self> {
+        Some(TestUpwardMessage::TransferKeepAlive(a, b))
+    }
+
+    fn transfer_all(a: AccountId, keep_alive: bool) -> Option<Self> {
+        Some(TestUpwardMessage::TransferAll(a, keep_alive))
     }
 }
 
 impl upward_messages::XCMPMessage for TestUpwardMessage {
-    fn send_message(_dest: ParaId, _msg: Vec<u8>) -> Self {
-        TestUpwardMessage {}
+    fn send_message(dest: ParaId, msg: Vec<u8>) -> Option<Self> {
+        Some(TestUpwardMessage::SendMessage(dest, msg))
+    }
+}
+
+thread_local! {
+    static SENT_UPWARD_MESSAGES: RefCell<Vec<(TestUpwardMessage, UpwardMessageOrigin)>> = RefCell::new(vec![]);
+    static SENT_XCMP_MESSAGES: RefCell<Vec<(ParaId, Vec<u8>)>> = RefCell::new(vec![]);
+    static DUST_POLICY: RefCell<DustPolicy<AccountId>> = RefCell::new(DustPolicy::Accumulate);
+    static FILTERED_ACCOUNTS: RefCell<Vec<AccountId>> = RefCell::new(vec![]);
+    static TRANSFER_DELAY: RefCell<u64> = RefCell::new(10);
+    static QUARANTINE_PERIOD: RefCell<u64> = RefCell::new(20);
+}
+
+pub struct TestDustPolicy;
+impl Get<DustPolicy<AccountId>> for TestDustPolicy {
+    fn get() -> DustPolicy<AccountId> {
+        DUST_POLICY.with(|p| p.borrow().clone())
+    }
+}
+impl TestDustPolicy {
+    /// Change the policy set with `ExtBuilder::dust_policy`.
+    pub fn set(dust_policy: DustPolicy<AccountId>) {
+        DUST_POLICY.with(|p| *p.borrow_mut() = dust_policy);
+    }
+}
+
+/// Transfer delay set with `ExtBuilder::transfer_delay`, 10 blocks unless set.
+pub struct TestTransferDelay;
+impl Get<u64> for TestTransferDelay {
+    fn get() -> u64 {
+        TRANSFER_DELAY.with(|d| *d.borrow())
+    }
+}
+
+/// Quarantine period set with `ExtBuilder::quarantine_period`, 20 blocks unless set.
+pub struct TestQuarantinePeriod;
+impl Get<u64> for TestQuarantinePeriod {
+    fn get() -> u64 {
+        QUARANTINE_PERIOD.with(|p| *p.borrow())
+    }
+}
+
+/// Rejects transfers from or to the accounts set with `ExtBuilder::filtered_accounts`.
+pub struct TestTransferFilter;
+impl TransferFilter<AccountId, AssetId, Balance> for TestTransferFilter {
+    fn allow_outbound(
+        from: &AccountId,
+        _: Counterparty,
+        dest: &AccountId,
+        _: &Option<AssetId>,
+        _: Balance,
+    ) -> bool {
+        FILTERED_ACCOUNTS.with(|a| !a.borrow().iter().any(|who| who == from || who == dest))
+    }
+
+    fn allow_inbound(_: Counterparty, dest: &AccountId, _: &Option<AssetId>, _: Balance) -> bool {
+        FILTERED_ACCOUNTS.with(|a| !a.borrow().contains(dest))
     }
 }
 
+/// Upward messages sent by the `MessageBrokerMock` so far.
+pub fn sent_upward_messages() -> Vec<(TestUpwardMessage, UpwardMessageOrigin)> {
+    SENT_UPWARD_MESSAGES.with(|m| m.borrow().clone())
+}
+
+/// Encoded XCMP messages sent by the `MessageBrokerMock` so far.
+pub fn sent_xcmp_messages() -> Vec<(ParaId, Vec<u8>)> {
+    SENT_XCMP_MESSAGES.with(|m| m.borrow().clone())
+}
+
 pub struct MessageBrokerMock {}
 impl UpwardMessageSender<TestUpwardMessage> for MessageBrokerMock {
-    fn send_upward_message(
-        _msg: &TestUpwardMessage,
-        _origin: UpwardMessageOrigin,
-    ) -> Result<(), ()> {
+    fn send_upward_message(msg: &TestUpwardMessage, origin: UpwardMessageOrigin) -> Result<(), ()> {
+        SENT_UPWARD_MESSAGES.with(|m| m.borrow_mut().push((msg.clone(), origin)));
         Ok(())
     }
 }
 
-impl XCMPMessageSender<XCMPMessage<AccountId, Balance, AssetId>> for MessageBrokerMock {
-    fn send_xcmp_message(
-        _dest: ParaId,
-        _msg: &XCMPMessage<AccountId, Balance, AssetId>,
-    ) -> Result<(), ()> {
+impl XCMPMessageSender<VersionedXcm> for MessageBrokerMock {
+    fn send_xcmp_message(dest: ParaId, msg: &VersionedXcm) -> Result<(), ()> {
+        SENT_XCMP_MESSAGES.with(|m| m.borrow_mut().push((dest, msg.encode())));
         Ok(())
     }
 }
@@ -127,12 +218,86 @@ impl pallet_balances::Trait for Test {
     type WeightInfo = ();
 }
 
+impl pallet_transaction_payment::Trait for Test {
+    type Currency = Balances;
+    type OnTransactionPayment = ();
+    type TransactionByteFee = TransactionByteFee;
+    type WeightToFee = IdentityFee<Balance>;
+    type FeeMultiplierUpdate = ();
+}
+
 impl Trait for Test {
     type UpwardMessageSender = MessageBrokerMock;
     type UpwardMessage = TestUpwardMessage;
     type XCMPMessageSender = MessageBrokerMock;
     type Event = TestEvent;
     type Currency = Balances;
+    type RelayTransferOrigin = RelayTransferOrigin;
+    type XCMPRoutingOrigin = XCMPRoutingOrigin;
+    type SelfParaId = SelfParaId;
+    type RelayExistentialDeposit = RelayExistentialDeposit;
+    type CreditLimitWarning = CreditLimitWarning;
+    type SovereignAccountOf = IndexedSovereignAccounts;
+    type PreviousSovereignAccountOf = PreviousSovereignAccounts;
+    type DustPolicy = TestDustPolicy;
+    type AssetMinimumBalance = AssetMinimumBalance;
+    type FeeHandler = ();
+    type AssetFeeHandler = TreasuryFees;
+    type FeeRateOracle = ();
+    type AdminOrigin = system::EnsureOneOf<
+        AccountId,
+        system::EnsureRoot<AccountId>,
+        system::EnsureSignedBy<Admins, AccountId>,
+    >;
+    type FundsOrigin = system::EnsureRoot<AccountId>;
+    type PauseOrigin = system::EnsureSignedBy<PauseGuardian, AccountId>;
+    type TransferDelay = TestTransferDelay;
+    type MaxDelayedTransfers = MaxDelayedTransfers;
+    type DelayedTransferWeight = DelayedTransferWeight;
+    type TransferFilter = TestTransferFilter;
+    type QuarantinePeriod = TestQuarantinePeriod;
+    type MaxQuarantineReleases = MaxQuarantineReleases;
+    type QuarantineReleaseWeight = QuarantineReleaseWeight;
+}
+
+/// Account allowed to configure the pallet besides root, but not to move its funds.
+pub const ADMIN: [u8; 32] = [7u8; 32];
+
+pub struct Admins;
+impl Contains<AccountId> for Admins {
+    fn sorted_members() -> Vec<AccountId> {
+        vec![ADMIN.into()]
+    }
+}
+
+/// Account allowed to pause and unpause transfers besides root.
+pub const PAUSE_GUARDIAN: [u8; 32] = [9u8; 32];
+
+pub struct PauseGuardian;
+impl Contains<AccountId> for PauseGuardian {
+    fn sorted_members() -> Vec<AccountId> {
+        vec![PAUSE_GUARDIAN.into()]
+    }
+}
+
+/// Account receiving the fees paid in assets.
+pub const TREASURY: [u8; 32] = [8u8; 32];
+
+pub struct TreasuryFees;
+impl AssetFeeHandler<AccountId, AssetId> for TreasuryFees {
+    fn fee_account(_: Counterparty, _: &AssetId) -> AccountId {
+        TREASURY.into()
+    }
+}
+
+/// Sovereign accounts derived before `IndexedSovereignAccounts`.
+pub struct PreviousSovereignAccounts;
+impl SovereignAccountOf<AccountId> for PreviousSovereignAccounts {
+    fn sovereign_account(counterparty: Counterparty, purpose: AccountPurpose) -> AccountId {
+        (b"previous", counterparty, purpose)
+            .using_encoded(sp_io::hashing::blake2_256)
+            .into()
+    }
 }
 
 mod token_dealer {
@@ -140,6 +305,12 @@ mod token_dealer {
 }
 
 use frame_system as system;
+impl_outer_dispatch! {
+    pub enum TestCall for Test where origin: Origin {
+        token_dealer::TokenDealer,
+    }
+}
+
 impl_outer_event! {
     pub enum TestEvent for Test {
         system<T>,
@@ -159,6 +330,27 @@ pub struct ExtBuilder {
     //spending_to_relay_rate: u128,
     //generic_to_spending_rate: u128,
     account_balances: Vec<(AccountId, Balance)>,
+    backing: Vec<(Counterparty, Option<AssetId>, Balance)>,
+    relay_float: Balance,
+    asset_locations: Vec<(Option<AssetId>, MultiLocation)>,
+    relay_call_indices: Option<RelayCallIndices>,
+    xcmp_transports: Vec<(ParaId, XCMPTransport)>,
+    opening_balances: Vec<(Counterparty, Option<AssetId>, Balance)>,
+    credit_limits: Vec<(ParaId, Option<AssetId>, Balance)>,
+    transfer_fees: Vec<(Counterparty, Option<AssetId>, TransferFee<Balance>)>,
+    asset_fee_rates: Vec<(AssetId, FixedU128)>,
+    outflow_limits: Vec<(Option<AssetId>, RateLimit<Balance, u64>)>,
+    minimum_transfers: Vec<(Counterparty, Option<AssetId>, Balance)>,
+    delay_thresholds: Vec<(Option<AssetId>, Balance)>,
+    quarantine_thresholds: Vec<(Option<AssetId>, Balance)>,
+    account_tiers: Vec<(AccountId, u8)>,
+    tier_quotas: Vec<(u8, Option<AssetId>, Quota<Balance, u64>)>,
+    parachain_access: Vec<(ParaId, Direction, Access)>,
+    allowlist_required: Vec<(Direction, bool)>,
+    dust_policy: DustPolicy<AccountId>,
+    filtered_accounts: Vec<AccountId>,
+    transfer_delay: u64,
+    quarantine_period: u64,
 }
 
 // Returns default values for genesis config
@@ -168,6 +360,27 @@ impl Default for ExtBuilder {
             // spending_to_relay_rate: 1000,
             // generic_to_spending_rate: 1,
             account_balances: vec![],
+            backing: vec![],
+            relay_float: 0,
+            asset_locations: vec![],
+            relay_call_indices: None,
+            xcmp_transports: vec![],
+            opening_balances: vec![],
+            credit_limits: vec![],
+            transfer_fees: vec![],
+            asset_fee_rates: vec![],
+            outflow_limits: vec![],
+            minimum_transfers: vec![],
+            delay_thresholds: vec![],
+            quarantine_thresholds: vec![],
+            account_tiers: vec![],
+            tier_quotas: vec![],
+            parachain_access: vec![],
+            allowlist_required: vec![],
+            dust_policy: DustPolicy::Accumulate,
+            filtered_accounts: vec![],
+            transfer_delay: 10,
+            quarantine_period: 20,
         }
     }
 }
@@ -183,7 +396,118 @@ impl ExtBuilder {
         self.account_balances = ab;
         self
     }
+    pub fn backing(mut self, backing: Vec<(Counterparty, Option<AssetId>, Balance)>) -> Self {
+        self.backing = backing;
+        self
+    }
+    pub fn relay_float(mut self, relay_float: Balance) -> Self {
+        self.relay_float = relay_float;
+        self
+    }
+    pub fn asset_locations(
+        mut self,
+        asset_locations: Vec<(Option<AssetId>, MultiLocation)>,
+    ) -> Self {
+        self.asset_locations = asset_locations;
+        self
+    }
+    pub fn relay_call_indices(mut self, relay_call_indices: Option<RelayCallIndices>) -> Self {
+        self.relay_call_indices = relay_call_indices;
+        self
+    }
+    pub fn xcmp_transports(mut self, xcmp_transports: Vec<(ParaId, XCMPTransport)>) -> Self {
+        self.xcmp_transports = xcmp_transports;
+        self
+    }
+    pub fn opening_balances(
+        mut self,
+        opening_balances: Vec<(Counterparty, Option<AssetId>, Balance)>,
+    ) -> Self {
+        self.opening_balances = opening_balances;
+        self
+    }
+    pub fn credit_limits(mut self, credit_limits: Vec<(ParaId, Option<AssetId>, Balance)>) -> Self {
+        self.credit_limits = credit_limits;
+        self
+    }
+    pub fn transfer_fees(
+        mut self,
+        transfer_fees: Vec<(Counterparty, Option<AssetId>, TransferFee<Balance>)>,
+    ) -> Self {
+        self.transfer_fees = transfer_fees;
+        self
+    }
+    pub fn asset_fee_rates(mut self, asset_fee_rates: Vec<(AssetId, FixedU128)>) -> Self {
+        self.asset_fee_rates = asset_fee_rates;
+        self
+    }
+    pub fn outflow_limits(
+        mut self,
+        outflow_limits: Vec<(Option<AssetId>, RateLimit<Balance, u64>)>,
+    ) -> Self {
+        self.outflow_limits = outflow_limits;
+        self
+    }
+    pub fn minimum_transfers(
+        mut self,
+        minimum_transfers: Vec<(Counterparty, Option<AssetId>, Balance)>,
+    ) -> Self {
+        self.minimum_transfers = minimum_transfers;
+        self
+    }
+    pub fn delay_thresholds(mut self, delay_thresholds: Vec<(Option<AssetId>, Balance)>) -> Self {
+        self.delay_thresholds = delay_thresholds;
+        self
+    }
+    pub fn quarantine_thresholds(
+        mut self,
+        quarantine_thresholds: Vec<(Option<AssetId>, Balance)>,
+    ) -> Self {
+        self.quarantine_thresholds = quarantine_thresholds;
+        self
+    }
+    pub fn account_tiers(mut self, account_tiers: Vec<(AccountId, u8)>) -> Self {
+        self.account_tiers = account_tiers;
+        self
+    }
+    pub fn tier_quotas(
+        mut self,
+        tier_quotas: Vec<(u8, Option<AssetId>, Quota<Balance, u64>)>,
+    ) -> Self {
+        self.tier_quotas = tier_quotas;
+        self
+    }
+    pub fn parachain_access(mut self, parachain_access: Vec<(ParaId, Direction, Access)>) -> Self {
+        self.parachain_access = parachain_access;
+        self
+    }
+    pub fn allowlist_required(mut self, allowlist_required: Vec<(Direction, bool)>) -> Self {
+        self.allowlist_required = allowlist_required;
+        self
+    }
+    pub fn dust_policy(mut self, dust_policy: DustPolicy<AccountId>) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
+    pub fn filtered_accounts(mut self, filtered_accounts: Vec<AccountId>) -> Self {
+        self.filtered_accounts = filtered_accounts;
+        self
+    }
+    pub fn transfer_delay(mut self, transfer_delay: u64) -> Self {
+        self.transfer_delay = transfer_delay;
+        self
+    }
+    pub fn quarantine_period(mut self, quarantine_period: u64) -> Self {
+        self.quarantine_period = quarantine_period;
+        self
+    }
     pub fn build(self) -> sp_io::TestExternalities {
+        SENT_UPWARD_MESSAGES.with(|m| m.borrow_mut().clear());
+        SENT_XCMP_MESSAGES.with(|m| m.borrow_mut().clear());
+        DUST_POLICY.with(|p| *p.borrow_mut() = self.dust_policy.clone());
+        FILTERED_ACCOUNTS.with(|a| *a.borrow_mut() = self.filtered_accounts.clone());
+        TRANSFER_DELAY.with(|d| *d.borrow_mut() = self.transfer_delay);
+        QUARANTINE_PERIOD.with(|p| *p.borrow_mut() = self.quarantine_period);
         let mut t = frame_system::GenesisConfig::default()
             .build_storage::<Test>()
             .unwrap();
@@ -192,6 +516,27 @@ impl ExtBuilder {
         }
         .assimilate_storage(&mut t)
         .unwrap();
+        GenesisConfig::<Test> {
+            backing: self.backing,
+            relay_float: self.relay_float,
+            asset_locations: self.asset_locations,
+            relay_call_indices: self.relay_call_indices,
+            xcmp_transports: self.xcmp_transports,
+            opening_balances: self.opening_balances,
+            credit_limits: self.credit_limits,
+            transfer_fees: self.transfer_fees,
+            asset_fee_rates: self.asset_fee_rates,
+            outflow_limits: self.outflow_limits,
+            minimum_transfers: self.minimum_transfers,
+            delay_thresholds: self.delay_thresholds,
+            quarantine_thresholds: self.quarantine_thresholds,
+            account_tiers: self.account_tiers,
+            tier_quotas: self.tier_quotas,
+            parachain_access: self.parachain_access,
+            allowlist_required: self.allowlist_required,
+        }
+        .assimilate_storage(&mut t)
+        .unwrap();
 
         let mut ext = sp_io::TestExternalities::new(t);
         ext.execute_with(|| System::set_block_number(1));
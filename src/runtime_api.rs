@@ -0,0 +1,17 @@
+//! Runtime API of the token dealer.
+
+use crate::Reconciliation;
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    /// API to reconcile the accounts held on this parachain for other chains.
+    pub trait TokenDealerApi<AssetId, Balance> where
+        AssetId: Codec,
+        Balance: Codec,
+    {
+        /// The flows with counterparties which do not match the balance of their accounts, see
+        /// `Module::reconciliation_discrepancies`.
+        fn reconciliation_discrepancies() -> Vec<Reconciliation<AssetId, Balance>>;
+    }
+}
@@ -20,12 +20,65 @@
 
 #![cfg(test)]
 use super::*;
+use crate::fee_payment::ChargeFeeInAsset;
 use crate::mock::{
-    AccountId, Assets, Balances, ExtBuilder, Origin, System, TestEvent, TokenDealer,
+    sent_upward_messages, sent_xcmp_messages, AccountId, Assets, Balances, DelayedTransferWeight,
+    ExtBuilder, Origin, PreviousSovereignAccounts, QuarantineReleaseWeight,
+    RelayExistentialDeposit, System, Test, TestCall, TestDustPolicy, TestEvent, TestUpwardMessage,
+    TokenDealer, ADMIN, PAUSE_GUARDIAN, TREASURY,
+};
+use frame_support::{
+    assert_noop, assert_ok,
+    traits::{LockableCurrency, OnInitialize, WithdrawReasons},
+    weights::{DispatchInfo, PostDispatchInfo},
+};
+use sp_runtime::{
+    traits::SignedExtension, transaction_validity::InvalidTransaction, DispatchError,
 };
-use frame_support::assert_ok;
 use sp_std::convert::TryInto;
 
+/// A sibling parachain's view of the asset `id` of the test parachain.
+fn asset_location(id: u128) -> MultiLocation {
+    MultiLocation::X3(
+        Junction::Parent,
+        Junction::Parachain { id: 100 },
+        Junction::GeneralIndex { id },
+    )
+}
+
+fn account_location(id: [u8; 32]) -> MultiLocation {
+    MultiLocation::X1(Junction::AccountId32 {
+        network: NetworkId::Any,
+        id,
+    })
+}
+
+fn reserve_asset_deposit(location: MultiLocation, amount: u128, dest: [u8; 32]) -> VersionedXcm {
+    VersionedXcm::V0(Xcm::ReserveAssetDeposit {
+        assets: vec![MultiAsset::ConcreteFungible {
+            id: location,
+            amount,
+        }],
+        effects: vec![Order::DepositAsset {
+            assets: vec![MultiAsset::All],
+            dest: account_location(dest),
+        }],
+    })
+}
+
+fn withdraw_asset(location: MultiLocation, amount: u128, dest: [u8; 32]) -> VersionedXcm {
+    VersionedXcm::V0(Xcm::WithdrawAsset {
+        assets: vec![MultiAsset::ConcreteFungible {
+            id: location,
+            amount,
+        }],
+        effects: vec![Order::DepositAsset {
+            assets: vec![MultiAsset::All],
+            dest: account_location(dest),
+        }],
+    })
+}
+
 fn encoded_to_remark(v: Vec<u8>) -> [u8; 32] {
     let boxed_slice = v.into_boxed_slice();
     let boxed_array: Box<[u8; 32]> = match boxed_slice.try_into() {
@@ -48,9 +101,12 @@ fn transfer_token_to_relay_settles_on_parachain_with_event() {
         asset_id,
         to.into(),
         transfer_amount,
+        0,
     ));
 
     ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, None, transfer_amount)])
+        .relay_float(transfer_amount)
         .free_balance(vec![(from.into(), initial_amount)])
         .build()
         .execute_with(|| {
@@ -86,25 +142,30 @@ fn transfer_assets_to_relay_settles_on_parachain_with_event() {
         asset_id,
         to.into(),
         transfer_amount,
+        0,
     ));
 
-    ExtBuilder::default().build().execute_with(|| {
-        assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
-        assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
-            Origin::signed(from.into()),
-            to.into(),
-            transfer_amount,
-            asset_id
-        ));
-        assert_eq!(
-            Assets::balance(0, from.into()),
-            initial_amount - transfer_amount
-        );
-        assert_eq!(Assets::balance(0, relay_account.into()), transfer_amount);
-        assert!(System::events()
-            .iter()
-            .any(|record| record.event == expected_event));
-    });
+    ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, asset_id, transfer_amount)])
+        .relay_float(transfer_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                asset_id
+            ));
+            assert_eq!(
+                Assets::balance(0, from.into()),
+                initial_amount - transfer_amount
+            );
+            assert_eq!(Assets::balance(0, relay_account.into()), transfer_amount);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
 }
 
 #[test]
@@ -186,12 +247,18 @@ fn transfer_tokens_to_para_settles_accounts_on_parachain_with_event() {
         asset_id_local,
         para_id,
         dest.into(),
-        asset_id_local,
         transfer_amount,
+        0,
     ));
+    let expected_msg = reserve_asset_deposit(asset_location(1), transfer_amount, dest);
     ExtBuilder::default().build().execute_with(|| {
         assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
         assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            asset_id_local,
+            asset_location(1)
+        ));
         assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
             Origin::signed(from.into()),
             para_id.into(),
@@ -207,12 +274,38 @@ fn transfer_tokens_to_para_settles_accounts_on_parachain_with_event() {
             Assets::balance(asset_id_local.unwrap(), from.into()),
             initial_amount - transfer_amount
         );
+        assert_eq!(sent_xcmp_messages(), vec![(para_id, expected_msg.encode())]);
+        assert_eq!(
+            TokenDealer::backing(Counterparty::Parachain(para_id), asset_id_local),
+            0
+        );
         assert!(System::events()
             .iter()
             .any(|record| record.event == expected_event));
     });
 }
 
+#[test]
+fn transfer_unregistered_asset_to_para_fails() {
+    let from = [0u8; 32];
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+        assert_noop!(
+            TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                from.into(),
+                initial_amount,
+                Some(0),
+            ),
+            Error::<Test>::AssetNotRegistered
+        );
+    });
+}
+
 #[test]
 fn make_transfer_to_relay_settles_accounts() {
     let from = [0u8; 32];
@@ -224,6 +317,8 @@ fn make_transfer_to_relay_settles_accounts() {
     let relay_account: [u8; 32] = RelayAccount::default().into_account();
     let relay_account: AccountId = relay_account.into();
     ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, asset_id_local, transfer_amount)])
+        .relay_float(transfer_amount)
         .free_balance(vec![(from.clone(), initial_amount)])
         .build()
         .execute_with(|| {
@@ -247,19 +342,22 @@ fn make_transfer_to_para_settles_accounts() {
     let initial_amount = 10000;
     let transfer_amount = 9000;
     let asset_id_local = Some(1);
-    let asset_id_dest = Some(3);
     let para_id: ParaId = 200.into();
     let dest = [0u8; 32];
 
     ExtBuilder::default().build().execute_with(|| {
         assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
         assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            asset_id_local,
+            asset_location(1)
+        ));
         assert_ok!(TokenDealer::make_transfer_to_parachain(
             &from.into(),
             &asset_id_local,
             para_id.into(),
             &dest.into(),
-            &asset_id_dest,
             transfer_amount,
         ));
         assert_eq!(
@@ -279,7 +377,7 @@ fn handle_xcmp_transfer_token_message_settles_accounts_on_parachain_with_event()
     let initial_amount = 10000;
     let transfer_amount = 9000;
     let asset_id = None;
-    let msg = XCMPMessage::TransferToken(dest.into(), transfer_amount, asset_id);
+    let msg = withdraw_asset(asset_location(0), transfer_amount, dest);
     let para_id: ParaId = 200.into();
     let para_account: [u8; 32] = para_id.into_account();
     let expected_event = TestEvent::token_dealer(RawEvent::TransferredTokensViaXCMP(
@@ -296,6 +394,11 @@ fn handle_xcmp_transfer_token_message_settles_accounts_on_parachain_with_event()
         .execute_with(|| {
             let dest_account: AccountId = dest.into();
             let para_account_id: AccountId = para_account.into();
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                asset_id,
+                asset_location(0)
+            ));
             TokenDealer::handle_xcmp_message(para_id, &msg);
             assert_eq!(Balances::free_balance(dest_account), transfer_amount);
             assert_eq!(
@@ -308,14 +411,13 @@ fn handle_xcmp_transfer_token_message_settles_accounts_on_parachain_with_event()
         });
 }
 
-// Nope
 #[test]
 fn handle_xcmp_transfer_assets_message_settles_accounts_on_parachain_with_event() {
     let dest = [0u8; 32];
     let initial_amount = 10000;
     let transfer_amount = 9000;
     let asset_id = Some(0);
-    let msg = XCMPMessage::TransferToken(dest.into(), transfer_amount, asset_id);
+    let msg = withdraw_asset(asset_location(1), transfer_amount, dest);
     let para_id: ParaId = 200.into();
     let para_account: [u8; 32] = para_id.into_account();
     let expected_event = TestEvent::token_dealer(RawEvent::TransferredTokensViaXCMP(
@@ -331,6 +433,11 @@ fn handle_xcmp_transfer_assets_message_settles_accounts_on_parachain_with_event(
             Origin::signed(para_account.into()),
             initial_amount
         ));
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            asset_id,
+            asset_location(1)
+        ));
         TokenDealer::handle_xcmp_message(para_id, &msg);
         assert_eq!(
             Assets::balance(asset_id.unwrap(), para_id.into_account()),
@@ -345,3 +452,2807 @@ fn handle_xcmp_transfer_assets_message_settles_accounts_on_parachain_with_event(
             .any(|record| record.event == expected_event));
     });
 }
+
+#[test]
+fn encoded_relay_call_uses_configured_indices() {
+    let dest = [1u8; 32];
+    let transfer_amount = 1000;
+    let para_id: ParaId = 200.into();
+    let indices = RelayCallIndices {
+        balances: 4,
+        balances_transfer: 0,
+        balances_transfer_keep_alive: 3,
+        balances_transfer_all: None,
+        parachains: 9,
+        parachains_send_xcmp_message: 2,
+    };
+    let expected_event = TestEvent::token_dealer(RawEvent::RelayCallIndicesUpdated(indices));
+
+    ExtBuilder::default().build().execute_with(|| {
+        let unset: Option<EncodedRelayCall<Test>> =
+            BalancesMessage::transfer(dest.into(), transfer_amount);
+        assert!(unset.is_none());
+        assert_noop!(
+            TokenDealer::set_relay_call_indices(Origin::signed(dest.into()), indices),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(TokenDealer::set_relay_call_indices(Origin::root(), indices));
+
+        let transfer: EncodedRelayCall<Test> =
+            BalancesMessage::transfer(dest.into(), transfer_amount).unwrap();
+        assert_eq!(
+            transfer.encode(),
+            (
+                4u8,
+                0u8,
+                AccountId::from(dest),
+                codec::Compact(transfer_amount)
+            )
+                .encode()
+        );
+        let transfer_keep_alive: EncodedRelayCall<Test> =
+            BalancesMessage::transfer_keep_alive(dest.into(), transfer_amount).unwrap();
+        assert_eq!(
+            transfer_keep_alive.encode(),
+            (
+                4u8,
+                3u8,
+                AccountId::from(dest),
+                codec::Compact(transfer_amount)
+            )
+                .encode()
+        );
+        let transfer_all: Option<EncodedRelayCall<Test>> =
+            BalancesMessage::transfer_all(dest.into(), true);
+        assert!(transfer_all.is_none());
+        let send: EncodedRelayCall<Test> =
+            upward_messages::XCMPMessage::send_message(para_id, vec![1, 2, 3]).unwrap();
+        assert_eq!(send.encode(), (9u8, 2u8, para_id, vec![1u8, 2, 3]).encode());
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == expected_event));
+    });
+}
+
+#[test]
+fn relay_routed_transfer_to_para_is_sent_as_upward_message() {
+    let from = [0u8; 32];
+    let initial_amount = 10000;
+    let transfer_amount = 9000;
+    let para_id: ParaId = 200.into();
+    let dest = [1u8; 32];
+    let msg = reserve_asset_deposit(asset_location(0), transfer_amount, dest);
+
+    ExtBuilder::default()
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            transfer_amount,
+        )])
+        .free_balance(vec![(from.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_ok!(TokenDealer::set_xcmp_transport(
+                Origin::root(),
+                para_id.into(),
+                XCMPTransport::RelayRouted
+            ));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                transfer_amount,
+                None,
+            ));
+            let para_account: AccountId = para_id.into_account();
+            assert_eq!(Balances::free_balance(para_account), transfer_amount);
+            assert!(sent_xcmp_messages().is_empty());
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::SendMessage(para_id, msg.encode()),
+                    UpwardMessageOrigin::Signed
+                )]
+            );
+        });
+}
+
+#[test]
+fn treasury_transfer_to_relay_is_sent_with_parachain_origin() {
+    let dest = [1u8; 32];
+    let transfer_amount = 1000;
+    let expected_event = TestEvent::token_dealer(RawEvent::TreasuryTransferredToRelayChain(
+        dest.into(),
+        transfer_amount,
+    ));
+
+    ExtBuilder::default()
+        .relay_float(transfer_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::transfer_treasury_to_relay_chain(
+                    Origin::signed(dest.into()),
+                    dest.into(),
+                    transfer_amount
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::transfer_treasury_to_relay_chain(
+                Origin::root(),
+                dest.into(),
+                transfer_amount
+            ));
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::Transfer(dest.into(), transfer_amount),
+                    UpwardMessageOrigin::Parachain
+                )]
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn transfer_token_to_relay_keep_alive_sends_keep_alive_transfer() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+
+    ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, None, transfer_amount)])
+        .relay_float(transfer_amount)
+        .free_balance(vec![(from.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain_keep_alive(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                None
+            ));
+            let relay_account: AccountId = relay_account.into();
+            assert_eq!(Balances::free_balance(relay_account), transfer_amount);
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::TransferKeepAlive(to.into(), transfer_amount),
+                    UpwardMessageOrigin::Signed
+                )]
+            );
+        });
+}
+
+#[test]
+fn withdraw_all_from_relay_is_sent_with_parachain_origin() {
+    let dest = [1u8; 32];
+    let expected_event =
+        TestEvent::token_dealer(RawEvent::WithdrewAllFromRelayChain(dest.into(), true));
+
+    ExtBuilder::default()
+        .relay_float(1000)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::withdraw_all_from_relay_chain(
+                    Origin::signed(dest.into()),
+                    dest.into(),
+                    true
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::withdraw_all_from_relay_chain(
+                Origin::root(),
+                dest.into(),
+                true
+            ));
+            assert_eq!(TokenDealer::relay_float(), RelayExistentialDeposit::get());
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::TransferAll(dest.into(), true),
+                    UpwardMessageOrigin::Parachain
+                )]
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn downward_opaque_credit_settles_accounts_on_parachain_with_event() {
+    let initial_amount = 10000;
+    let transfer_amount = 4000;
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let payload =
+        VersionedDownwardPayload::<AccountId, u128, u32>::V0(DownwardPayload::Credit(vec![
+            (dest.into(), None, transfer_amount),
+            (dest.into(), Some(0), transfer_amount),
+        ]));
+    let downward_message = DownwardMessage::Opaque(payload.encode());
+    let expected_event = TestEvent::token_dealer(RawEvent::TransferredTokensFromRelayChain(
+        dest.into(),
+        transfer_amount,
+        Some(0),
+        Ok(()),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(
+                Origin::signed(relay_account.into()),
+                initial_amount
+            ));
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(
+                Balances::free_balance(AccountId::from(dest)),
+                transfer_amount
+            );
+            assert_eq!(Assets::balance(0, dest.into()), transfer_amount);
+            assert_eq!(
+                Assets::balance(0, relay_account.into()),
+                initial_amount - transfer_amount
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn unrecognised_downward_message_emits_event() {
+    let downward_message = DownwardMessage::Opaque(vec![42, 1, 2, 3]);
+
+    ExtBuilder::default().build().execute_with(|| {
+        TokenDealer::handle_downward_message(&downward_message);
+        let expected_event = TestEvent::token_dealer(RawEvent::UnrecognisedDownwardMessage(
+            <Test as frame_system::Trait>::Hashing::hash_of(&downward_message),
+        ));
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == expected_event));
+    });
+}
+
+#[test]
+fn downward_payload_with_trailing_bytes_is_unrecognised() {
+    let initial_amount = 10000;
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let mut payload = VersionedDownwardPayload::<AccountId, u128, u32>::V0(
+        DownwardPayload::Credit(vec![(dest.into(), None, 1000)]),
+    )
+    .encode();
+    payload.push(0);
+    let downward_message = DownwardMessage::Opaque(payload);
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 0);
+            let expected_event = TestEvent::token_dealer(RawEvent::UnrecognisedDownwardMessage(
+                <Test as frame_system::Trait>::Hashing::hash_of(&downward_message),
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn register_asset_location_replaces_previous_location() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            Some(0),
+            asset_location(0)
+        ));
+        assert_noop!(
+            TokenDealer::register_asset_location(Origin::root(), Some(1), asset_location(0)),
+            Error::<Test>::LocationAlreadyRegistered
+        );
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            Some(0),
+            asset_location(1)
+        ));
+        assert_eq!(TokenDealer::location_asset(asset_location(0)), None);
+        assert_eq!(
+            TokenDealer::location_asset(asset_location(1)),
+            Some(Some(0))
+        );
+        assert_ok!(TokenDealer::deregister_asset_location(
+            Origin::root(),
+            Some(0)
+        ));
+        assert_eq!(TokenDealer::asset_location(Some(0)), None);
+        assert_eq!(TokenDealer::location_asset(asset_location(1)), None);
+    });
+}
+
+#[test]
+fn genesis_config_registers_asset_locations() {
+    let relay_token = MultiLocation::X1(Junction::Parent);
+    ExtBuilder::default()
+        .asset_locations(vec![
+            (Some(0), relay_token.clone()),
+            (None, asset_location(1)),
+        ])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::asset_location(Some(0)),
+                Some(relay_token.clone())
+            );
+            assert_eq!(TokenDealer::location_asset(relay_token), Some(Some(0)));
+            assert_eq!(TokenDealer::location_asset(asset_location(1)), Some(None));
+        });
+}
+
+#[test]
+fn genesis_config_sets_relay_call_indices() {
+    let indices = RelayCallIndices {
+        balances: 4,
+        balances_transfer: 0,
+        balances_transfer_keep_alive: 3,
+        balances_transfer_all: Some(4),
+        parachains: 9,
+        parachains_send_xcmp_message: 2,
+    };
+    ExtBuilder::default()
+        .relay_call_indices(Some(indices))
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::relay_call_indices(), Some(indices));
+        });
+}
+
+#[test]
+fn genesis_config_sets_xcmp_transports() {
+    let para_id: ParaId = 200.into();
+    ExtBuilder::default()
+        .xcmp_transports(vec![(para_id, XCMPTransport::RelayRouted)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::xcmp_transport(para_id),
+                XCMPTransport::RelayRouted
+            );
+            assert_eq!(
+                TokenDealer::xcmp_transport(ParaId::from(300)),
+                XCMPTransport::Direct
+            );
+        });
+}
+
+#[test]
+fn genesis_config_sets_backing() {
+    let counterparty = Counterparty::Parachain(200.into());
+    ExtBuilder::default()
+        .backing(vec![(counterparty, Some(0), 1000)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::backing(counterparty, Some(0)), 1000);
+            assert_eq!(TokenDealer::backing(counterparty, None), 0);
+        });
+}
+
+#[test]
+fn genesis_config_sets_relay_float() {
+    ExtBuilder::default()
+        .relay_float(1000)
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::relay_float(), 1000);
+        });
+}
+
+#[test]
+fn genesis_config_sets_opening_balances() {
+    ExtBuilder::default()
+        .opening_balances(vec![(Counterparty::Relay, None, 5000)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::opening_balance(Counterparty::Relay, None),
+                5000
+            );
+        });
+}
+
+#[test]
+fn genesis_config_sets_credit_limits() {
+    let para_id: ParaId = 200.into();
+    ExtBuilder::default()
+        .credit_limits(vec![(para_id, None, 1000)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::credit_limit(para_id, None), Some(1000));
+            assert_eq!(TokenDealer::credit_limit(para_id, Some(0)), None);
+        });
+}
+
+#[test]
+fn genesis_config_sets_transfer_fees() {
+    let fee = TransferFee {
+        flat: 50,
+        rate: Perbill::from_percent(10),
+    };
+    ExtBuilder::default()
+        .transfer_fees(vec![(Counterparty::Relay, None, fee)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::transfer_fee(Counterparty::Relay, None),
+                Some(fee)
+            );
+        });
+}
+
+#[test]
+fn genesis_config_sets_asset_fee_rates() {
+    let rate = FixedU128::saturating_from_integer(2);
+    ExtBuilder::default()
+        .asset_fee_rates(vec![(0, rate)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::asset_fee_rate(0), Some(rate));
+        });
+}
+
+#[test]
+fn genesis_config_sets_outflow_limits() {
+    let limit = RateLimit {
+        amount: 1000,
+        window: 10,
+    };
+    ExtBuilder::default()
+        .outflow_limits(vec![(None, limit)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::outflow_limit(None), Some(limit));
+        });
+}
+
+#[test]
+fn genesis_config_sets_minimum_transfers() {
+    ExtBuilder::default()
+        .minimum_transfers(vec![(Counterparty::Relay, None, 100)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::minimum_transfer(Counterparty::Relay, None),
+                100
+            );
+        });
+}
+
+#[test]
+fn genesis_config_sets_delay_thresholds() {
+    ExtBuilder::default()
+        .delay_thresholds(vec![(None, 5000)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::delay_threshold(None), Some(5000));
+        });
+}
+
+#[test]
+fn genesis_config_sets_quarantine_thresholds() {
+    ExtBuilder::default()
+        .quarantine_thresholds(vec![(Some(0), 500)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::quarantine_threshold(Some(0)), Some(500));
+        });
+}
+
+#[test]
+fn genesis_config_sets_account_tiers() {
+    let account: AccountId = [1u8; 32].into();
+    ExtBuilder::default()
+        .account_tiers(vec![(account.clone(), 2)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::account_tier(account), 2);
+        });
+}
+
+#[test]
+fn genesis_config_sets_tier_quotas() {
+    let quota = Quota {
+        amount: 1000,
+        period: 10,
+    };
+    ExtBuilder::default()
+        .tier_quotas(vec![(2, None, quota)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(TokenDealer::tier_quota(2, None), Some(quota));
+            assert_eq!(TokenDealer::tier_quota(0, None), None);
+        });
+}
+
+#[test]
+fn genesis_config_sets_parachain_access() {
+    let para_id: ParaId = 200.into();
+    ExtBuilder::default()
+        .parachain_access(vec![(para_id, Direction::Inbound, Access::Denied)])
+        .build()
+        .execute_with(|| {
+            assert_eq!(
+                TokenDealer::parachain_access(para_id, Direction::Inbound),
+                Some(Access::Denied)
+            );
+            assert_eq!(
+                TokenDealer::parachain_access(para_id, Direction::Outbound),
+                None
+            );
+        });
+}
+
+#[test]
+fn genesis_config_sets_allowlist_required() {
+    ExtBuilder::default()
+        .allowlist_required(vec![(Direction::Outbound, true)])
+        .build()
+        .execute_with(|| {
+            assert!(TokenDealer::allowlist_required(Direction::Outbound));
+            assert!(!TokenDealer::allowlist_required(Direction::Inbound));
+        });
+}
+
+#[test]
+fn handle_xcmp_message_with_unknown_asset_emits_event() {
+    let dest = [0u8; 32];
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let msg = withdraw_asset(asset_location(5), 1000, dest);
+    let expected_event = TestEvent::token_dealer(RawEvent::UnrecognisedXCMPMessage(
+        para_id,
+        <Test as frame_system::Trait>::Hashing::hash_of(&msg),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            let para_account: AccountId = para_id.into_account();
+            assert_eq!(Balances::free_balance(para_account), initial_amount);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn handle_xcmp_reserve_asset_deposit_of_asset_reserved_at_sender_is_credited() {
+    let dest = [0u8; 32];
+    let initial_amount = 10000;
+    let transfer_amount = 9000;
+    let para_id: ParaId = 200.into();
+    let sender_token = MultiLocation::X2(Junction::Parent, Junction::Parachain { id: 200 });
+    let msg = reserve_asset_deposit(sender_token.clone(), transfer_amount, dest);
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                sender_token
+            ));
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(
+                Balances::free_balance(AccountId::from(dest)),
+                transfer_amount
+            );
+        });
+}
+
+#[test]
+fn handle_xcmp_rejects_reserve_asset_deposit_of_asset_not_reserved_at_sender() {
+    let dest = [0u8; 32];
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let msg = reserve_asset_deposit(asset_location(0), 1000, dest);
+    let expected_event = TestEvent::token_dealer(RawEvent::UnrecognisedXCMPMessage(
+        para_id,
+        <Test as frame_system::Trait>::Hashing::hash_of(&msg),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            let para_account: AccountId = para_id.into_account();
+            assert_eq!(Balances::free_balance(para_account), initial_amount);
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 0);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn handle_xcmp_applies_each_deposit_asset_filter() {
+    let first = [1u8; 32];
+    let second = [2u8; 32];
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let msg = VersionedXcm::V0(Xcm::WithdrawAsset {
+        assets: vec![MultiAsset::ConcreteFungible {
+            id: asset_location(0),
+            amount: 9000,
+        }],
+        effects: vec![
+            Order::DepositAsset {
+                assets: vec![MultiAsset::ConcreteFungible {
+                    id: asset_location(0),
+                    amount: 3000,
+                }],
+                dest: account_location(first),
+            },
+            Order::DepositAsset {
+                assets: vec![MultiAsset::All],
+                dest: account_location(second),
+            },
+        ],
+    });
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(Balances::free_balance(AccountId::from(first)), 3000);
+            assert_eq!(Balances::free_balance(AccountId::from(second)), 6000);
+        });
+}
+
+#[test]
+fn handle_xcmp_rejects_message_leaving_assets_unmatched() {
+    let dest = [1u8; 32];
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let msg = VersionedXcm::V0(Xcm::WithdrawAsset {
+        assets: vec![MultiAsset::ConcreteFungible {
+            id: asset_location(0),
+            amount: 9000,
+        }],
+        effects: vec![Order::DepositAsset {
+            assets: vec![MultiAsset::ConcreteFungible {
+                id: asset_location(0),
+                amount: 3000,
+            }],
+            dest: account_location(dest),
+        }],
+    });
+    let expected_event = TestEvent::token_dealer(RawEvent::UnrecognisedXCMPMessage(
+        para_id,
+        <Test as frame_system::Trait>::Hashing::hash_of(&msg),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 0);
+            let para_account: AccountId = para_id.into_account();
+            assert_eq!(Balances::free_balance(para_account), initial_amount);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn relay_token_round_trips_between_siblings() {
+    let initial_amount = 10000;
+    let received_amount = 1000;
+    let returned_amount = 900;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let para_account: [u8; 32] = para_id.into_account();
+    let relay_token = MultiLocation::X1(Junction::Parent);
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                relay_token.clone()
+            ));
+            TokenDealer::handle_xcmp_message(
+                para_id,
+                &withdraw_asset(relay_token.clone(), received_amount, from),
+            );
+            assert_eq!(
+                Balances::free_balance(AccountId::from(from)),
+                received_amount
+            );
+
+            // the relay token is reserved on neither sibling, so it is sent back withdrawn
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                returned_amount,
+                None
+            ));
+            let msg = withdraw_asset(relay_token, returned_amount, dest);
+            assert_eq!(sent_xcmp_messages(), vec![(para_id, msg.encode())]);
+            assert_eq!(
+                Balances::free_balance(AccountId::from(para_account)),
+                initial_amount - received_amount + returned_amount
+            );
+
+            // which a sibling running this pallet accepts
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(
+                Balances::free_balance(AccountId::from(dest)),
+                returned_amount
+            );
+        });
+}
+
+#[test]
+fn transfers_are_limited_to_backing_received_from_counterparty() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(from.into(), transfer_amount, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .relay_float(transfer_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::InsufficientBacking
+            );
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(
+                TokenDealer::backing(Counterparty::Relay, None::<u32>),
+                transfer_amount
+            );
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+            assert_eq!(TokenDealer::backing(Counterparty::Relay, None::<u32>), 0);
+        });
+}
+
+#[test]
+fn reconciliation_reports_accounts_not_matching_flows() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(dest.into(), transfer_amount, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, transfer_amount)])
+        .relay_float(transfer_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+            // the initial balance of the relay account is not explained by any flow
+            assert_eq!(
+                TokenDealer::reconciliation_discrepancies(),
+                vec![Reconciliation {
+                    counterparty: Counterparty::Relay,
+                    asset_id: None,
+                    inflow: 0,
+                    outflow: transfer_amount,
+                    opening: 0,
+                    balance: initial_amount + transfer_amount,
+                }]
+            );
+
+            TokenDealer::handle_downward_message(&downward_message);
+            let relay_account: AccountId = relay_account.into();
+            Balances::make_free_balance_be(&relay_account, transfer_amount / 2);
+            assert_eq!(
+                TokenDealer::total_inflow(Counterparty::Relay, None::<u32>),
+                transfer_amount
+            );
+            assert_eq!(
+                TokenDealer::reconciliation_discrepancies()[0].balance,
+                transfer_amount / 2
+            );
+
+            Balances::make_free_balance_be(&relay_account, 0);
+            assert!(TokenDealer::reconciliation_discrepancies().is_empty());
+        });
+}
+
+#[test]
+fn reconciliation_accounts_for_opening_balances() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let expected_event = TestEvent::token_dealer(RawEvent::OpeningBalanceUpdated(
+        Counterparty::Relay,
+        None,
+        initial_amount,
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, transfer_amount)])
+        .relay_float(transfer_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::set_opening_balance(
+                    Origin::signed(from.into()),
+                    Counterparty::Relay,
+                    None,
+                    initial_amount
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_opening_balance(
+                Origin::root(),
+                Counterparty::Relay,
+                None,
+                initial_amount
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+            assert!(TokenDealer::reconciliation_discrepancies().is_empty());
+        });
+}
+
+#[test]
+fn reconciliation_covers_counterparties_without_flows() {
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+
+    ExtBuilder::default()
+        .free_balance(vec![(para_id.into_account(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_credit_limit(
+                Origin::root(),
+                para_id.into(),
+                None,
+                Some(initial_amount)
+            ));
+            assert_eq!(
+                TokenDealer::reconciliation_discrepancies(),
+                vec![Reconciliation {
+                    counterparty: Counterparty::Parachain(para_id),
+                    asset_id: None,
+                    inflow: 0,
+                    outflow: 0,
+                    opening: 0,
+                    balance: initial_amount,
+                }]
+            );
+        });
+}
+
+#[test]
+fn failed_transfer_to_relay_leaves_relay_float_untouched() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::InsufficientBacking
+            );
+            assert_eq!(TokenDealer::relay_float(), initial_amount);
+        });
+}
+
+#[test]
+fn transfers_to_relay_are_limited_to_relay_float() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(from.into(), transfer_amount, [0u8; 32]);
+    let expected_event = TestEvent::token_dealer(RawEvent::RelayFloatUpdated(initial_amount));
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::InsufficientRelayFloat
+            );
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(TokenDealer::relay_float(), transfer_amount);
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+            assert_eq!(TokenDealer::relay_float(), 0);
+
+            assert_noop!(
+                TokenDealer::set_relay_float(Origin::signed(from.into()), initial_amount),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_relay_float(Origin::root(), initial_amount));
+            assert_eq!(TokenDealer::relay_float(), initial_amount);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+
+            assert_ok!(TokenDealer::withdraw_all_from_relay_chain(
+                Origin::root(),
+                dest.into(),
+                false
+            ));
+            assert_eq!(TokenDealer::relay_float(), 0);
+        });
+}
+
+#[test]
+fn transfers_with_para_are_limited_by_credit_limit() {
+    let initial_amount = 10000;
+    let limit = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let approached_event =
+        TestEvent::token_dealer(RawEvent::CreditLimitApproached(para_id, None, 800, limit));
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            initial_amount,
+        )])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_ok!(TokenDealer::set_credit_limit(
+                Origin::root(),
+                para_id.into(),
+                None,
+                Some(limit)
+            ));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                700,
+                None,
+            ));
+            assert!(!System::events()
+                .iter()
+                .any(|record| record.event == approached_event));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                100,
+                None,
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == approached_event));
+            assert_noop!(
+                TokenDealer::transfer_assets_to_parachain_chain(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    dest.into(),
+                    201,
+                    None,
+                ),
+                Error::<Test>::CreditLimitExceeded
+            );
+
+            // inbound transfers reduce the net amount, down to minus the limit, and are
+            // parked beyond it, still backed by the counterparty
+            let msg = withdraw_asset(asset_location(0), 1801, dest);
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            let parked_event = TestEvent::token_dealer(RawEvent::CreditParked(
+                dest.into(),
+                Counterparty::Parachain(para_id),
+                None,
+                1801,
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == parked_event));
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 0);
+            assert_eq!(
+                TokenDealer::claim_of(
+                    AccountId::from(dest),
+                    (Counterparty::Parachain(para_id), None)
+                ),
+                1801
+            );
+            assert_eq!(
+                TokenDealer::backing(Counterparty::Parachain(para_id), None::<u32>),
+                initial_amount + 1801
+            );
+            assert_noop!(
+                TokenDealer::claim(
+                    Origin::signed(dest.into()),
+                    Counterparty::Parachain(para_id),
+                    None
+                ),
+                Error::<Test>::CreditLimitExceeded
+            );
+            let msg = withdraw_asset(asset_location(0), 700, dest);
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 700);
+        });
+}
+
+#[test]
+fn transfers_reducing_exposure_above_lowered_credit_limit_are_allowed() {
+    let initial_amount = 10000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            initial_amount,
+        )])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                800,
+                None,
+            ));
+            assert_ok!(TokenDealer::set_credit_limit(
+                Origin::root(),
+                para_id.into(),
+                None,
+                Some(500)
+            ));
+            assert_noop!(
+                TokenDealer::transfer_assets_to_parachain_chain(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    dest.into(),
+                    100,
+                    None,
+                ),
+                Error::<Test>::CreditLimitExceeded
+            );
+
+            // the exposure of 800 is above the limit, but an inbound transfer reduces it
+            let msg = withdraw_asset(asset_location(0), 200, dest);
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(Balances::free_balance(AccountId::from(dest)), 200);
+            assert_eq!(
+                TokenDealer::credit_exposure(
+                    Counterparty::Parachain(para_id),
+                    &None,
+                    Zero::zero(),
+                    Zero::zero()
+                ),
+                600
+            );
+        });
+}
+
+#[test]
+fn indexed_sovereign_accounts_round_trip() {
+    let para_id: ParaId = 200.into();
+    let relay_account: [u8; 32] = RelayAccount(0).into_account();
+    let mut legacy_relay_account = [0u8; 32];
+    legacy_relay_account[..5].copy_from_slice(b"Relay");
+    assert_eq!(relay_account, legacy_relay_account);
+    let sibling_account: [u8; 32] = SiblingAccount(para_id, 0).into_account();
+    let para_account: [u8; 32] = para_id.into_account();
+    assert_eq!(sibling_account, para_account);
+
+    let fees_account: [u8; 32] = RelayAccount(AccountPurpose::Fees.index()).into_account();
+    assert_ne!(fees_account, relay_account);
+    assert_eq!(
+        RelayAccount::try_from_account(&fees_account),
+        Some(RelayAccount(1))
+    );
+    let escrow_account: [u8; 32] =
+        SiblingAccount(para_id, AccountPurpose::Escrow.index()).into_account();
+    assert_eq!(
+        SiblingAccount::try_from_account(&escrow_account),
+        Some(SiblingAccount(para_id, 2))
+    );
+    assert_eq!(SiblingAccount::try_from_account(&fees_account), None);
+    assert_eq!(RelayAccount::try_from_account(&escrow_account), None);
+}
+
+#[test]
+fn migrate_sovereign_account_moves_funds_to_derived_account() {
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let counterparty = Counterparty::Parachain(para_id);
+    let old_account: AccountId =
+        PreviousSovereignAccounts::sovereign_account(counterparty, AccountPurpose::Transfers);
+    let para_account: AccountId = para_id.into_account();
+    let expected_event = TestEvent::token_dealer(RawEvent::SovereignAccountMigrated(
+        counterparty,
+        AccountPurpose::Transfers,
+        old_account.clone(),
+        para_account.clone(),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(old_account.clone(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(
+                Origin::signed(old_account.clone()),
+                initial_amount
+            ));
+            assert_noop!(
+                TokenDealer::migrate_sovereign_account(
+                    Origin::signed(old_account.clone()),
+                    counterparty,
+                    AccountPurpose::Transfers,
+                    vec![None, Some(0)],
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::migrate_sovereign_account(
+                Origin::root(),
+                counterparty,
+                AccountPurpose::Transfers,
+                vec![None, Some(0), None],
+            ));
+            assert_eq!(Balances::free_balance(para_account.clone()), initial_amount);
+            assert_eq!(Balances::free_balance(old_account.clone()), 0);
+            assert_eq!(Assets::balance(0, para_account), initial_amount);
+            assert_eq!(Assets::balance(0, old_account), 0);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn migrate_sovereign_account_moves_nothing_unless_every_balance_can_be_moved() {
+    let initial_amount = 10000;
+    let para_id: ParaId = 200.into();
+    let counterparty = Counterparty::Parachain(para_id);
+    let old_account: AccountId =
+        PreviousSovereignAccounts::sovereign_account(counterparty, AccountPurpose::Transfers);
+
+    ExtBuilder::default()
+        .free_balance(vec![(old_account.clone(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(
+                Origin::signed(old_account.clone()),
+                initial_amount
+            ));
+            Balances::set_lock(*b"locked  ", &old_account, 1, WithdrawReasons::all());
+            assert_noop!(
+                TokenDealer::migrate_sovereign_account(
+                    Origin::root(),
+                    counterparty,
+                    AccountPurpose::Transfers,
+                    vec![Some(0), None],
+                ),
+                pallet_balances::Error::<Test, pallet_balances::DefaultInstance>::LiquidityRestrictions
+            );
+            assert_eq!(Assets::balance(0, old_account), initial_amount);
+        });
+}
+
+#[test]
+fn inbound_dust_accumulates_until_existential_deposit() {
+    let initial_amount = 10000;
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(dest.into(), 60, [0u8; 32]);
+    let pending_event = TestEvent::token_dealer(RawEvent::CreditPending(
+        dest.into(),
+        Counterparty::Relay,
+        None,
+        60,
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            let dest: AccountId = dest.into();
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(Balances::free_balance(dest.clone()), 0);
+            assert_eq!(
+                TokenDealer::pending_credit(dest.clone(), (Counterparty::Relay, None::<u32>)),
+                60
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == pending_event));
+
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(Balances::free_balance(dest.clone()), 120);
+            assert_eq!(
+                TokenDealer::pending_credit(dest, (Counterparty::Relay, None::<u32>)),
+                0
+            );
+            assert_eq!(
+                TokenDealer::total_inflow(Counterparty::Relay, None::<u32>),
+                120
+            );
+            assert_eq!(TokenDealer::backing(Counterparty::Relay, None::<u32>), 120);
+        });
+}
+
+#[test]
+fn inbound_dust_is_routed_to_dust_account() {
+    let initial_amount = 10000;
+    let dest = [1u8; 32];
+    let dust_account = [2u8; 32];
+    let para_id: ParaId = 200.into();
+    let msg = withdraw_asset(asset_location(1), 5, dest);
+    let expected_event = TestEvent::token_dealer(RawEvent::DustRouted(
+        dest.into(),
+        Some(0),
+        5,
+        dust_account.into(),
+    ));
+
+    ExtBuilder::default()
+        .dust_policy(DustPolicy::RouteTo(dust_account.into()))
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(
+                Origin::signed(para_id.into_account()),
+                initial_amount
+            ));
+            assert_ok!(Assets::make_transfer(
+                &para_id.into_account(),
+                0,
+                &dust_account.into(),
+                1
+            ));
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                Some(0),
+                asset_location(1)
+            ));
+            TokenDealer::handle_xcmp_message(para_id, &msg);
+            assert_eq!(Assets::balance(0, dest.into()), 0);
+            assert_eq!(Assets::balance(0, dust_account.into()), 6);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn inbound_dust_stays_pending_until_dust_account_holds_asset() {
+    let initial_amount = 10000;
+    let dest = [1u8; 32];
+    let dust_account = [2u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(dest.into(), 60, [0u8; 32]);
+    let routed_event = TestEvent::token_dealer(RawEvent::DustRouted(
+        dest.into(),
+        None,
+        60,
+        dust_account.into(),
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .dust_policy(DustPolicy::RouteTo(dust_account.into()))
+        .build()
+        .execute_with(|| {
+            let dest: AccountId = dest.into();
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(Balances::free_balance(AccountId::from(dust_account)), 0);
+            assert_eq!(
+                TokenDealer::pending_credit(dest.clone(), (Counterparty::Relay, None::<u32>)),
+                60
+            );
+
+            // the pending credits can be routed once the dust account exists
+            Balances::make_free_balance_be(&dust_account.into(), 1000);
+            assert_ok!(TokenDealer::release_pending_credit(
+                Origin::signed(dust_account.into()),
+                dest.clone(),
+                Counterparty::Relay,
+                None
+            ));
+            assert_eq!(Balances::free_balance(AccountId::from(dust_account)), 1060);
+            assert_eq!(
+                TokenDealer::pending_credit(dest, (Counterparty::Relay, None::<u32>)),
+                0
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == routed_event));
+        });
+}
+
+#[test]
+fn pending_credits_are_released_under_changed_dust_policy() {
+    let initial_amount = 10000;
+    let dest = [1u8; 32];
+    let dust_account = [2u8; 32];
+    let caller = [3u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(dest.into(), 60, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (relay_account.into(), initial_amount),
+            (dust_account.into(), 1000),
+        ])
+        .build()
+        .execute_with(|| {
+            let dest: AccountId = dest.into();
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_noop!(
+                TokenDealer::release_pending_credit(
+                    Origin::signed(caller.into()),
+                    dest.clone(),
+                    Counterparty::Relay,
+                    None
+                ),
+                Error::<Test>::CreditStillPending
+            );
+
+            TestDustPolicy::set(DustPolicy::RouteTo(dust_account.into()));
+            assert_ok!(TokenDealer::release_pending_credit(
+                Origin::signed(caller.into()),
+                dest.clone(),
+                Counterparty::Relay,
+                None
+            ));
+            assert_eq!(Balances::free_balance(AccountId::from(dust_account)), 1060);
+            assert_eq!(
+                TokenDealer::total_inflow(Counterparty::Relay, None::<u32>),
+                60
+            );
+            assert_noop!(
+                TokenDealer::release_pending_credit(
+                    Origin::signed(caller.into()),
+                    dest,
+                    Counterparty::Relay,
+                    None
+                ),
+                Error::<Test>::NoPendingCredit
+            );
+        });
+}
+
+#[test]
+fn transfer_fee_is_charged_to_sender_and_reported() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let fee = TransferFee {
+        flat: 50,
+        rate: Perbill::from_percent(10),
+    };
+    let expected_event = TestEvent::token_dealer(RawEvent::TransferredTokensToRelayChain(
+        from.into(),
+        None,
+        to.into(),
+        transfer_amount,
+        150,
+    ));
+
+    ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, None, transfer_amount)])
+        .relay_float(transfer_amount)
+        .free_balance(vec![(from.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::set_transfer_fee(
+                    Origin::signed(from.into()),
+                    Counterparty::Relay,
+                    None,
+                    Some(fee)
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_transfer_fee(
+                Origin::root(),
+                Counterparty::Relay,
+                None,
+                Some(fee)
+            ));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                None
+            ));
+            let from: AccountId = from.into();
+            assert_eq!(
+                Balances::free_balance(from),
+                initial_amount - transfer_amount - 150
+            );
+            assert_eq!(Balances::total_issuance(), initial_amount - 150);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+        });
+}
+
+#[test]
+fn transfer_fee_is_refunded_when_transfer_fails() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let fee = TransferFee {
+        flat: 50,
+        rate: Perbill::from_percent(0),
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                MultiLocation::X1(Junction::Parent)
+            ));
+            assert_ok!(TokenDealer::set_transfer_fee(
+                Origin::root(),
+                Counterparty::Parachain(para_id),
+                None,
+                Some(fee)
+            ));
+            assert_noop!(
+                TokenDealer::transfer_assets_to_parachain_chain(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::InsufficientBacking
+            );
+            assert_eq!(
+                Balances::free_balance(AccountId::from(from)),
+                initial_amount
+            );
+        });
+}
+
+#[test]
+fn transfer_fee_is_paid_in_asset_with_fee_rate() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let asset_id = Some(0);
+    let rate = FixedU128::saturating_from_integer(2);
+    let fee = TransferFee {
+        flat: 50,
+        rate: Perbill::from_percent(0),
+    };
+    let fees_account: AccountId = TREASURY.into();
+    let expected_event = TestEvent::token_dealer(RawEvent::TransferredTokensToRelayChain(
+        from.into(),
+        asset_id,
+        to.into(),
+        transfer_amount,
+        100,
+    ));
+
+    ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, asset_id, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+            assert_ok!(TokenDealer::set_transfer_fee(
+                Origin::root(),
+                Counterparty::Relay,
+                asset_id,
+                Some(fee)
+            ));
+            assert_ok!(TokenDealer::set_asset_fee_rate(
+                Origin::root(),
+                0,
+                Some(rate)
+            ));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                asset_id
+            ));
+            assert_eq!(
+                Assets::balance(0, from.into()),
+                initial_amount - transfer_amount - 100
+            );
+            assert_eq!(Assets::balance(0, fees_account), 100);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    to.into(),
+                    initial_amount - transfer_amount - 150,
+                    asset_id
+                ),
+                Error::<Test>::InsufficientBalance
+            );
+        });
+}
+
+#[test]
+fn asset_fee_is_refunded_if_transfer_is_not_settled() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let asset_id = Some(0);
+    let fee = TransferFee {
+        flat: 50,
+        rate: Perbill::from_percent(0),
+    };
+
+    ExtBuilder::default()
+        .backing(vec![(Counterparty::Relay, asset_id, transfer_amount - 1)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+            assert_ok!(TokenDealer::set_transfer_fee(
+                Origin::root(),
+                Counterparty::Relay,
+                asset_id,
+                Some(fee)
+            ));
+            assert_ok!(TokenDealer::set_asset_fee_rate(
+                Origin::root(),
+                0,
+                Some(FixedU128::saturating_from_integer(2))
+            ));
+            assert_eq!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    to.into(),
+                    transfer_amount,
+                    asset_id
+                ),
+                Err(Error::<Test>::InsufficientBacking.into())
+            );
+            assert_eq!(Assets::balance(0, from.into()), initial_amount);
+            assert_eq!(Assets::balance(0, TREASURY.into()), 0);
+        });
+}
+
+#[test]
+fn charge_fee_in_asset_pays_extrinsic_fee_in_asset() {
+    let initial_amount = 10000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let info = DispatchInfo {
+        weight: 100,
+        ..Default::default()
+    };
+    let len = 10;
+    let call = TestCall::TokenDealer(Call::transfer_tokens_to_relay_chain(
+        to.into(),
+        1000,
+        Some(0),
+    ));
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Assets::issue(Origin::signed(from.into()), initial_amount));
+        assert_ok!(TokenDealer::set_asset_fee_rate(
+            Origin::root(),
+            0,
+            Some(FixedU128::saturating_from_integer(2))
+        ));
+        let who: AccountId = from.into();
+
+        // 110 in Currency for the weight and length, paid as 220 of the asset
+        let validity = ChargeFeeInAsset::<Test>::from(0)
+            .validate(&who, &call, &info, len)
+            .unwrap();
+        assert_eq!(validity.priority, 110);
+        let pre = ChargeFeeInAsset::<Test>::from(0)
+            .pre_dispatch(&who, &call, &info, len)
+            .unwrap();
+        assert!(pre.is_none());
+        assert_eq!(Assets::balance(0, from.into()), initial_amount - 220);
+        assert_eq!(Assets::balance(0, TREASURY.into()), 220);
+        assert_ok!(ChargeFeeInAsset::<Test>::post_dispatch(
+            pre,
+            &info,
+            &PostDispatchInfo::from(Some(50)),
+            len,
+            &Ok(())
+        ));
+        assert_eq!(Assets::balance(0, from.into()), initial_amount - 220);
+
+        assert_ok!(Assets::make_transfer(
+            &who,
+            0,
+            &to.into(),
+            initial_amount - 300
+        ));
+        assert_eq!(
+            ChargeFeeInAsset::<Test>::from(0).validate(&who, &call, &info, len),
+            Err(InvalidTransaction::Payment.into())
+        );
+    });
+}
+
+#[test]
+fn charge_fee_in_asset_pays_other_fees_in_currency_with_refund() {
+    let initial_amount = 10000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let info = DispatchInfo {
+        weight: 100,
+        ..Default::default()
+    };
+    let len = 10;
+    let call = TestCall::TokenDealer(Call::transfer_tokens_to_relay_chain(to.into(), 1000, None));
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            let who: AccountId = from.into();
+            let validity = ChargeFeeInAsset::<Test>::from(5)
+                .validate(&who, &call, &info, len)
+                .unwrap();
+            assert_eq!(validity.priority, 115);
+            let pre = ChargeFeeInAsset::<Test>::from(5)
+                .pre_dispatch(&who, &call, &info, len)
+                .unwrap();
+            assert!(pre.is_some());
+            assert_eq!(Balances::free_balance(who.clone()), initial_amount - 115);
+
+            // half the weight was used, its fee is refunded
+            assert_ok!(ChargeFeeInAsset::<Test>::post_dispatch(
+                pre,
+                &info,
+                &PostDispatchInfo::from(Some(50)),
+                len,
+                &Ok(())
+            ));
+            assert_eq!(Balances::free_balance(who), initial_amount - 65);
+        });
+}
+
+#[test]
+fn paused_outbound_transfers_fail_until_unpaused() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let scope = PauseScope::Counterparty(Counterparty::Parachain(para_id));
+    let expected_event =
+        TestEvent::token_dealer(RawEvent::TransfersPaused(scope, Direction::Outbound));
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            transfer_amount,
+        )])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_noop!(
+                TokenDealer::pause(Origin::signed(from.into()), scope, Direction::Outbound),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::pause(
+                Origin::root(),
+                scope,
+                Direction::Outbound
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected_event));
+            assert_noop!(
+                TokenDealer::transfer_assets_to_parachain_chain(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::TransfersPaused
+            );
+
+            assert_ok!(TokenDealer::unpause(
+                Origin::root(),
+                scope,
+                Direction::Outbound
+            ));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+        });
+}
+
+#[test]
+fn pause_origin_can_pause_but_not_configure() {
+    let guardian: AccountId = PAUSE_GUARDIAN.into();
+    let fee = TransferFee {
+        flat: 10,
+        rate: Perbill::from_percent(0),
+    };
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(TokenDealer::pause(
+            Origin::signed(guardian.clone()),
+            PauseScope::All,
+            Direction::Inbound
+        ));
+        assert!(TokenDealer::paused(PauseScope::All, Direction::Inbound));
+        assert_noop!(
+            TokenDealer::set_transfer_fee(
+                Origin::signed(guardian.clone()),
+                Counterparty::Relay,
+                None,
+                Some(fee)
+            ),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(TokenDealer::unpause(
+            Origin::signed(guardian),
+            PauseScope::All,
+            Direction::Inbound
+        ));
+        assert!(!TokenDealer::paused(PauseScope::All, Direction::Inbound));
+    });
+}
+
+#[test]
+fn admin_origin_can_configure_but_not_move_funds() {
+    let admin: AccountId = ADMIN.into();
+    let dest = [1u8; 32];
+    let fee = TransferFee {
+        flat: 10,
+        rate: Perbill::from_percent(0),
+    };
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(TokenDealer::set_transfer_fee(
+            Origin::signed(admin.clone()),
+            Counterparty::Relay,
+            None,
+            Some(fee)
+        ));
+        assert_noop!(
+            TokenDealer::transfer_treasury_to_relay_chain(
+                Origin::signed(admin.clone()),
+                dest.into(),
+                1000
+            ),
+            DispatchError::BadOrigin
+        );
+        assert_noop!(
+            TokenDealer::withdraw_all_from_relay_chain(
+                Origin::signed(admin.clone()),
+                dest.into(),
+                true
+            ),
+            DispatchError::BadOrigin
+        );
+        assert_noop!(
+            TokenDealer::migrate_sovereign_account(
+                Origin::signed(admin.clone()),
+                Counterparty::Relay,
+                AccountPurpose::Transfers,
+                vec![None],
+            ),
+            DispatchError::BadOrigin
+        );
+        assert_noop!(
+            TokenDealer::return_quarantined(Origin::signed(admin), 0, dest.into()),
+            DispatchError::BadOrigin
+        );
+    });
+}
+
+#[test]
+fn paused_inbound_transfers_are_parked_until_claimed() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let scope = PauseScope::Asset(Some(0));
+    let msg = withdraw_asset(asset_location(1), transfer_amount, dest);
+    let parked_event = TestEvent::token_dealer(RawEvent::CreditParked(
+        dest.into(),
+        Counterparty::Parachain(para_id),
+        Some(0),
+        transfer_amount,
+    ));
+    let claimed_event = TestEvent::token_dealer(RawEvent::Claimed(
+        dest.into(),
+        Counterparty::Parachain(para_id),
+        Some(0),
+        transfer_amount,
+    ));
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Assets::issue(
+            Origin::signed(para_id.into_account()),
+            initial_amount
+        ));
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            Some(0),
+            asset_location(1)
+        ));
+        assert_ok!(TokenDealer::pause(
+            Origin::root(),
+            scope,
+            Direction::Inbound
+        ));
+        TokenDealer::handle_xcmp_message(para_id, &msg);
+        assert_eq!(Assets::balance(0, dest.into()), 0);
+        assert_eq!(
+            TokenDealer::claim_of(
+                AccountId::from(dest),
+                (Counterparty::Parachain(para_id), Some(0))
+            ),
+            transfer_amount
+        );
+        assert_eq!(
+            TokenDealer::backing(Counterparty::Parachain(para_id), Some(0)),
+            transfer_amount
+        );
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == parked_event));
+        assert_noop!(
+            TokenDealer::claim(
+                Origin::signed(dest.into()),
+                Counterparty::Parachain(para_id),
+                Some(0)
+            ),
+            Error::<Test>::TransfersPaused
+        );
+
+        assert_ok!(TokenDealer::unpause(
+            Origin::root(),
+            scope,
+            Direction::Inbound
+        ));
+        assert_ok!(TokenDealer::claim(
+            Origin::signed(dest.into()),
+            Counterparty::Parachain(para_id),
+            Some(0)
+        ));
+        assert_eq!(Assets::balance(0, dest.into()), transfer_amount);
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == claimed_event));
+        assert_noop!(
+            TokenDealer::claim(
+                Origin::signed(dest.into()),
+                Counterparty::Parachain(para_id),
+                Some(0)
+            ),
+            Error::<Test>::NothingToClaim
+        );
+    });
+}
+
+#[test]
+fn transfers_to_denylisted_parachains_fail() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            transfer_amount,
+        )])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_noop!(
+                TokenDealer::set_parachain_access(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    Direction::Outbound,
+                    Some(Access::Denied)
+                ),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_parachain_access(
+                Origin::root(),
+                para_id.into(),
+                Direction::Outbound,
+                Some(Access::Denied)
+            ));
+            assert_noop!(
+                TokenDealer::transfer_assets_to_parachain_chain(
+                    Origin::signed(from.into()),
+                    para_id.into(),
+                    dest.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::ParachainNotPermitted
+            );
+
+            assert_ok!(TokenDealer::set_parachain_access(
+                Origin::root(),
+                para_id.into(),
+                Direction::Outbound,
+                None
+            ));
+            assert_ok!(TokenDealer::transfer_assets_to_parachain_chain(
+                Origin::signed(from.into()),
+                para_id.into(),
+                dest.into(),
+                transfer_amount,
+                None
+            ));
+        });
+}
+
+#[test]
+fn xcmp_messages_from_unlisted_parachains_are_rejected_when_allowlist_required() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let dest = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let msg = withdraw_asset(asset_location(1), transfer_amount, dest);
+    let expected_event = TestEvent::token_dealer(RawEvent::XCMPMessageRejected(
+        para_id,
+        <Test as frame_system::Trait>::Hashing::hash_of(&msg),
+    ));
+
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Assets::issue(
+            Origin::signed(para_id.into_account()),
+            initial_amount
+        ));
+        assert_ok!(TokenDealer::register_asset_location(
+            Origin::root(),
+            Some(0),
+            asset_location(1)
+        ));
+        assert_ok!(TokenDealer::set_allowlist_required(
+            Origin::root(),
+            Direction::Inbound,
+            true
+        ));
+        TokenDealer::handle_xcmp_message(para_id, &msg);
+        assert_eq!(Assets::balance(0, dest.into()), 0);
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == expected_event));
+
+        assert_ok!(TokenDealer::set_parachain_access(
+            Origin::root(),
+            para_id.into(),
+            Direction::Inbound,
+            Some(Access::Allowed)
+        ));
+        TokenDealer::handle_xcmp_message(para_id, &msg);
+        assert_eq!(Assets::balance(0, dest.into()), transfer_amount);
+    });
+}
+
+#[test]
+fn outflows_are_rate_limited_over_rolling_window() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let limit = RateLimit {
+        amount: 1500,
+        window: 10,
+    };
+    let transfer = || {
+        TokenDealer::transfer_tokens_to_relay_chain(
+            Origin::signed(from.into()),
+            dest.into(),
+            transfer_amount,
+            None,
+        )
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::set_outflow_limit(Origin::signed(from.into()), None, Some(limit)),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_outflow_limit(
+                Origin::root(),
+                None,
+                Some(limit)
+            ));
+            System::set_block_number(9);
+            assert_ok!(transfer());
+            assert_noop!(transfer(), Error::<Test>::OutflowRateLimitExceeded);
+
+            // the window spans multiples of its length
+            System::set_block_number(10);
+            assert_noop!(transfer(), Error::<Test>::OutflowRateLimitExceeded);
+            System::set_block_number(18);
+            assert_noop!(transfer(), Error::<Test>::OutflowRateLimitExceeded);
+
+            System::set_block_number(19);
+            assert_ok!(transfer());
+            assert_eq!(
+                TokenDealer::recent_outflows(None::<u32>),
+                vec![(19, transfer_amount)]
+            );
+        });
+}
+
+#[test]
+fn transfers_are_limited_to_tier_quota_per_period() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let quota = Quota {
+        amount: 1500,
+        period: 10,
+    };
+    let transfer = |from: [u8; 32]| {
+        TokenDealer::transfer_tokens_to_relay_chain(
+            Origin::signed(from.into()),
+            dest.into(),
+            transfer_amount,
+            None,
+        )
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (dest.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::set_tier_quota(Origin::signed(from.into()), 1, None, Some(quota)),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::set_tier_quota(
+                Origin::root(),
+                1,
+                None,
+                Some(quota)
+            ));
+            assert_ok!(TokenDealer::set_account_tier(
+                Origin::root(),
+                from.into(),
+                1
+            ));
+            assert_ok!(transfer(from));
+            System::set_block_number(9);
+            assert_noop!(transfer(from), Error::<Test>::QuotaExceeded);
+            assert_ok!(transfer(dest));
+            assert_ok!(transfer(dest));
+
+            System::set_block_number(10);
+            assert_ok!(transfer(from));
+            assert_eq!(
+                TokenDealer::account_outflows(AccountId::from(from), None::<u32>),
+                (10, transfer_amount)
+            );
+        });
+}
+
+#[test]
+fn amounts_below_minimum_transfer_are_rejected_or_parked() {
+    let initial_amount = 10000;
+    let from = [0u8; 32];
+    let dest = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(dest.into(), 200, [0u8; 32]);
+    let parked_event = TestEvent::token_dealer(RawEvent::CreditParked(
+        dest.into(),
+        Counterparty::Relay,
+        None,
+        200,
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_minimum_transfer(
+                Origin::root(),
+                Counterparty::Relay,
+                None,
+                500
+            ));
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    dest.into(),
+                    100,
+                    None
+                ),
+                Error::<Test>::BelowMinimumTransfer
+            );
+
+            TokenDealer::handle_downward_message(&downward_message);
+            let dest: AccountId = dest.into();
+            assert_eq!(Balances::free_balance(dest.clone()), 0);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == parked_event));
+            assert_noop!(
+                TokenDealer::claim(Origin::signed(dest.clone()), Counterparty::Relay, None),
+                Error::<Test>::BelowMinimumTransfer
+            );
+
+            // parked amounts are claimable once they add up to the minimum
+            TokenDealer::handle_downward_message(&downward_message);
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_ok!(TokenDealer::claim(
+                Origin::signed(dest.clone()),
+                Counterparty::Relay,
+                None
+            ));
+            assert_eq!(Balances::free_balance(dest), 600);
+        });
+}
+
+#[test]
+fn large_transfers_are_delayed_and_executed_on_initialize() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let escrow: AccountId =
+        TokenDealer::sovereign_account(Counterparty::Relay, AccountPurpose::Escrow);
+    let queued_event = TestEvent::token_dealer(RawEvent::TransferQueued(
+        0,
+        from.into(),
+        Counterparty::Relay,
+        None,
+        to.into(),
+        transfer_amount,
+        11,
+    ));
+    let executed_event = TestEvent::token_dealer(RawEvent::DelayedTransferExecuted(0, Ok(())));
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_delay_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                None
+            ));
+            assert_eq!(Balances::free_balance(escrow.clone()), transfer_amount);
+            assert!(sent_upward_messages().is_empty());
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == queued_event));
+
+            TokenDealer::on_initialize(10);
+            assert!(TokenDealer::pending_transfer(0).is_some());
+            assert_eq!(TokenDealer::on_initialize(11), DelayedTransferWeight::get());
+            assert!(TokenDealer::pending_transfer(0).is_none());
+            assert_eq!(Balances::free_balance(escrow), 0);
+            assert_eq!(
+                Balances::free_balance(AccountId::from(relay_account)),
+                transfer_amount
+            );
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::Transfer(to.into(), transfer_amount),
+                    UpwardMessageOrigin::Signed
+                )]
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == executed_event));
+        });
+}
+
+#[test]
+fn delayed_transfers_can_be_cancelled_by_sender_or_vetoed() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let transfer = || {
+        TokenDealer::transfer_assets_to_parachain_chain(
+            Origin::signed(from.into()),
+            para_id.into(),
+            to.into(),
+            transfer_amount,
+            None,
+        )
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(
+            Counterparty::Parachain(para_id),
+            None,
+            initial_amount,
+        )])
+        .build()
+        .execute_with(|| {
+            let from: AccountId = from.into();
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_ok!(TokenDealer::set_delay_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            assert_ok!(transfer());
+            assert_ok!(transfer());
+            assert_eq!(TokenDealer::transfer_queue(11), vec![0, 1]);
+            assert_eq!(
+                Balances::free_balance(from.clone()),
+                initial_amount - 2 * transfer_amount
+            );
+
+            assert_noop!(
+                TokenDealer::cancel_transfer(Origin::signed(to.into()), 0),
+                Error::<Test>::NotTransferSender
+            );
+            assert_ok!(TokenDealer::cancel_transfer(
+                Origin::signed(from.clone()),
+                0
+            ));
+            assert_noop!(
+                TokenDealer::veto_transfer(Origin::signed(from.clone()), 1),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::veto_transfer(Origin::root(), 1));
+            assert_noop!(
+                TokenDealer::cancel_transfer(Origin::signed(from.clone()), 1),
+                Error::<Test>::UnknownTransfer
+            );
+            assert_eq!(TokenDealer::transfer_queue(11), Vec::<u64>::new());
+            assert_eq!(Balances::free_balance(from), initial_amount);
+            assert!(System::events().iter().any(|record| record.event
+                == TestEvent::token_dealer(RawEvent::DelayedTransferVetoed(1))));
+        });
+}
+
+#[test]
+fn delayed_transfers_are_executed_next_block_without_transfer_delay() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let queued_event = TestEvent::token_dealer(RawEvent::TransferQueued(
+        0,
+        from.into(),
+        Counterparty::Relay,
+        None,
+        to.into(),
+        transfer_amount,
+        2,
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .transfer_delay(0)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_delay_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            assert_ok!(TokenDealer::transfer_tokens_to_relay_chain(
+                Origin::signed(from.into()),
+                to.into(),
+                transfer_amount,
+                None
+            ));
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == queued_event));
+
+            TokenDealer::on_initialize(2);
+            assert!(TokenDealer::pending_transfer(0).is_none());
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::Transfer(to.into(), transfer_amount),
+                    UpwardMessageOrigin::Signed
+                )]
+            );
+        });
+}
+
+#[test]
+fn delayed_transfers_beyond_block_limit_are_executed_later() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let transfer = || {
+        TokenDealer::transfer_tokens_to_relay_chain(
+            Origin::signed(from.into()),
+            to.into(),
+            transfer_amount,
+            None,
+        )
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_delay_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            assert_ok!(transfer());
+            assert_ok!(transfer());
+            // the third transfer is delayed to the next block with room
+            assert_ok!(transfer());
+            assert_eq!(TokenDealer::transfer_queue(11), vec![0, 1]);
+            assert_eq!(TokenDealer::transfer_queue(12), vec![2]);
+            assert_eq!(TokenDealer::pending_transfer(2).unwrap().execute_at, 12);
+
+            // cancelled transfers do not let later transfers jump the queue
+            assert_ok!(TokenDealer::cancel_transfer(Origin::signed(from.into()), 0));
+            System::set_block_number(2);
+            assert_ok!(transfer());
+            assert_ok!(transfer());
+            assert_eq!(TokenDealer::transfer_queue(11), vec![1]);
+            assert_eq!(TokenDealer::transfer_queue(12), vec![2, 3]);
+            assert_eq!(TokenDealer::transfer_queue(13), vec![4]);
+            assert_eq!(
+                TokenDealer::on_initialize(12),
+                2 * DelayedTransferWeight::get()
+            );
+        });
+}
+
+#[test]
+fn transfers_failing_checks_are_not_delayed() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let to = [1u8; 32];
+    let para_id: ParaId = 200.into();
+    let transfer_to_relay = || {
+        TokenDealer::transfer_tokens_to_relay_chain(
+            Origin::signed(from.into()),
+            to.into(),
+            transfer_amount,
+            None,
+        )
+    };
+    let transfer_to_para = || {
+        TokenDealer::transfer_assets_to_parachain_chain(
+            Origin::signed(from.into()),
+            para_id.into(),
+            to.into(),
+            transfer_amount,
+            None,
+        )
+    };
+
+    ExtBuilder::default()
+        .free_balance(vec![(from.into(), initial_amount)])
+        .backing(vec![
+            (Counterparty::Relay, None, transfer_amount - 1),
+            (Counterparty::Parachain(para_id), None, initial_amount),
+        ])
+        .relay_float(initial_amount)
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_delay_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            assert_noop!(transfer_to_relay(), Error::<Test>::InsufficientBacking);
+            assert_noop!(transfer_to_para(), Error::<Test>::AssetNotRegistered);
+
+            assert_ok!(TokenDealer::register_asset_location(
+                Origin::root(),
+                None,
+                asset_location(0)
+            ));
+            assert_ok!(TokenDealer::pause(
+                Origin::root(),
+                PauseScope::Counterparty(Counterparty::Parachain(para_id)),
+                Direction::Outbound
+            ));
+            assert_noop!(transfer_to_para(), Error::<Test>::TransfersPaused);
+        });
+}
+
+#[test]
+fn transfer_filter_rejects_outbound_and_parks_inbound() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let from = [0u8; 32];
+    let filtered = [3u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message =
+        DownwardMessage::TransferInto(filtered.into(), transfer_amount, [0u8; 32]);
+    let parked_event = TestEvent::token_dealer(RawEvent::CreditParked(
+        filtered.into(),
+        Counterparty::Relay,
+        None,
+        transfer_amount,
+    ));
+
+    ExtBuilder::default()
+        .free_balance(vec![
+            (from.into(), initial_amount),
+            (relay_account.into(), initial_amount),
+        ])
+        .backing(vec![(Counterparty::Relay, None, initial_amount)])
+        .relay_float(initial_amount)
+        .filtered_accounts(vec![filtered.into()])
+        .build()
+        .execute_with(|| {
+            assert_noop!(
+                TokenDealer::transfer_tokens_to_relay_chain(
+                    Origin::signed(from.into()),
+                    filtered.into(),
+                    transfer_amount,
+                    None
+                ),
+                Error::<Test>::TransferFiltered
+            );
+
+            TokenDealer::handle_downward_message(&downward_message);
+            let filtered: AccountId = filtered.into();
+            assert_eq!(Balances::free_balance(filtered.clone()), 0);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == parked_event));
+            assert_noop!(
+                TokenDealer::claim(Origin::signed(filtered), Counterparty::Relay, None),
+                Error::<Test>::TransferFiltered
+            );
+        });
+}
+
+#[test]
+fn large_inbound_credits_are_quarantined_and_released_on_initialize() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let to = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(to.into(), transfer_amount, [0u8; 32]);
+    let quarantined_event = TestEvent::token_dealer(RawEvent::CreditQuarantined(
+        0,
+        to.into(),
+        Counterparty::Relay,
+        None,
+        transfer_amount,
+        21,
+    ));
+    let released_event = TestEvent::token_dealer(RawEvent::QuarantineReleased(0, Ok(())));
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            let to: AccountId = to.into();
+            assert_ok!(TokenDealer::set_quarantine_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(Balances::free_balance(to.clone()), 0);
+            assert_eq!(TokenDealer::quarantine_queue(21), vec![0]);
+            assert_eq!(
+                TokenDealer::backing(Counterparty::Relay, None::<u32>),
+                transfer_amount
+            );
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == quarantined_event));
+
+            TokenDealer::on_initialize(20);
+            assert!(TokenDealer::quarantined(0).is_some());
+            assert_eq!(
+                TokenDealer::on_initialize(21),
+                QuarantineReleaseWeight::get()
+            );
+            assert!(TokenDealer::quarantined(0).is_none());
+            assert_eq!(Balances::free_balance(to), transfer_amount);
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == released_event));
+        });
+}
+
+#[test]
+fn quarantined_credits_are_released_next_block_without_quarantine_period() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let to = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(to.into(), transfer_amount, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .quarantine_period(0)
+        .build()
+        .execute_with(|| {
+            let to: AccountId = to.into();
+            assert_ok!(TokenDealer::set_quarantine_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_eq!(TokenDealer::quarantine_queue(2), vec![0]);
+
+            TokenDealer::on_initialize(2);
+            assert!(TokenDealer::quarantined(0).is_none());
+            assert_eq!(Balances::free_balance(to), transfer_amount);
+        });
+}
+
+#[test]
+fn quarantined_credits_beyond_block_limit_are_released_later() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let to = [1u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(to.into(), transfer_amount, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            let to: AccountId = to.into();
+            assert_ok!(TokenDealer::set_quarantine_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            for _ in 0..5 {
+                TokenDealer::handle_downward_message(&downward_message);
+            }
+            assert_eq!(TokenDealer::quarantine_queue(21), vec![0, 1]);
+            assert_eq!(TokenDealer::quarantine_queue(22), vec![2, 3]);
+            assert_eq!(TokenDealer::quarantine_queue(23), vec![4]);
+            assert_eq!(TokenDealer::quarantined(4).unwrap().release_at, 23);
+            assert_eq!(TokenDealer::last_quarantine_block(), 23);
+
+            assert_eq!(
+                TokenDealer::on_initialize(21),
+                2 * QuarantineReleaseWeight::get()
+            );
+            assert_eq!(Balances::free_balance(to.clone()), 2 * transfer_amount);
+            TokenDealer::on_initialize(22);
+            TokenDealer::on_initialize(23);
+            assert_eq!(Balances::free_balance(to), 5 * transfer_amount);
+        });
+}
+
+#[test]
+fn quarantined_credits_can_be_returned_by_governance() {
+    let initial_amount = 10000;
+    let transfer_amount = 1000;
+    let to = [1u8; 32];
+    let sender = [2u8; 32];
+    let relay_account: [u8; 32] = RelayAccount::default().into_account();
+    let downward_message = DownwardMessage::TransferInto(to.into(), transfer_amount, [0u8; 32]);
+
+    ExtBuilder::default()
+        .free_balance(vec![(relay_account.into(), initial_amount)])
+        .build()
+        .execute_with(|| {
+            assert_ok!(TokenDealer::set_quarantine_threshold(
+                Origin::root(),
+                None,
+                Some(500)
+            ));
+            TokenDealer::handle_downward_message(&downward_message);
+            assert_noop!(
+                TokenDealer::return_quarantined(Origin::signed(to.into()), 0, sender.into()),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(TokenDealer::return_quarantined(
+                Origin::root(),
+                0,
+                sender.into()
+            ));
+            assert_noop!(
+                TokenDealer::release_quarantined(Origin::root(), 0),
+                Error::<Test>::UnknownQuarantine
+            );
+            assert_eq!(TokenDealer::quarantine_queue(21), Vec::<u64>::new());
+            assert_eq!(TokenDealer::backing(Counterparty::Relay, None::<u32>), 0);
+            assert_eq!(TokenDealer::relay_float(), 0);
+            assert_eq!(
+                sent_upward_messages(),
+                vec![(
+                    TestUpwardMessage::Transfer(sender.into(), transfer_amount),
+                    UpwardMessageOrigin::Signed
+                )]
+            );
+            assert!(System::events().iter().any(|record| record.event
+                == TestEvent::token_dealer(RawEvent::QuarantineReturned(0, sender.into()))));
+        });
+}
@@ -20,10 +20,18 @@
 //! type is different for each of them. To support all of them, Cumulus provides
 //! traits to write upward message generic code.
 
+use codec::{Compact, Decode, Encode, Input, Output};
 use polkadot_parachain::primitives::Id as ParaId;
-use sp_std::vec::Vec;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::RuntimeDebug;
+use sp_std::{marker::PhantomData, vec::Vec};
 
-use polkadot_core_primitives::{AccountId, Balance};
+use crate::{convert_hack, BalanceOf, Module, Trait};
+#[cfg(feature = "relay-runtimes")]
+use polkadot_core_primitives::AccountId;
+use polkadot_core_primitives::Balance;
+#[cfg(feature = "relay-runtimes")]
 use rococo_runtime::{BalancesCall, ParachainsCall};
 // mod kusama;
 // mod polkadot;
@@ -33,26 +41,162 @@ use rococo_runtime::{BalancesCall, ParachainsCall};
 pub trait BalancesMessage<AccountId, Balance>: Sized {
     /// Transfer the given `amount` from the parachain account to the given
     /// `dest` account.
-    fn transfer(dest: AccountId, amount: Balance) -> Self;
+    ///
+    /// Returns `None` if the call cannot be encoded.
+    fn transfer(dest: AccountId, amount: Balance) -> Option<Self>;
+
+    /// Transfer the given `amount` from the parachain account to the given
+    /// `dest` account, failing rather than reaping the parachain account.
+    ///
+    /// Returns `None` if the call cannot be encoded.
+    fn transfer_keep_alive(dest: AccountId, amount: Balance) -> Option<Self>;
+
+    /// Transfer the whole balance of the parachain account to the given `dest`
+    /// account, leaving the existential deposit if `keep_alive`.
+    ///
+    /// Returns `None` if the Relay Chain has no such call.
+    fn transfer_all(dest: AccountId, keep_alive: bool) -> Option<Self>;
 }
 
 /// A `XCMP` related upward message.
 pub trait XCMPMessage: Sized {
     /// Send the given XCMP message to given parachain.
-    fn send_message(dest: ParaId, msg: Vec<u8>) -> Self;
+    ///
+    /// Returns `None` if the call cannot be encoded.
+    fn send_message(dest: ParaId, msg: Vec<u8>) -> Option<Self>;
 }
 
 /// The Rococo upward message.
+#[cfg(feature = "relay-runtimes")]
 pub type RococoUpwardMessage = rococo_runtime::Call;
 
+#[cfg(feature = "relay-runtimes")]
 impl BalancesMessage<AccountId, Balance> for RococoUpwardMessage {
-    fn transfer(dest: AccountId, amount: Balance) -> Self {
-        BalancesCall::transfer(dest, amount).into()
+    fn transfer(dest: AccountId, amount: Balance) -> Option<Self> {
+        Some(BalancesCall::transfer(dest, amount).into())
+    }
+
+    fn transfer_keep_alive(dest: AccountId, amount: Balance) -> Option<Self> {
+        Some(BalancesCall::transfer_keep_alive(dest, amount).into())
+    }
+
+    /// The Rococo `Balances` pallet has no `transfer_all`.
+    fn transfer_all(_dest: AccountId, _keep_alive: bool) -> Option<Self> {
+        None
     }
 }
 
+#[cfg(feature = "relay-runtimes")]
 impl XCMPMessage for RococoUpwardMessage {
-    fn send_message(dest: ParaId, msg: Vec<u8>) -> Self {
-        ParachainsCall::send_xcmp_message(dest, msg).into()
+    fn send_message(dest: ParaId, msg: Vec<u8>) -> Option<Self> {
+        Some(ParachainsCall::send_xcmp_message(dest, msg).into())
+    }
+}
+
+/// Pallet and call indices of the Relay Chain calls built by [`EncodedRelayCall`].
+///
+/// These follow the `construct_runtime!` order of the Relay Chain runtime and have to be updated
+/// with `set_relay_call_indices` whenever a Relay Chain runtime upgrade reorders them. No call is
+/// encoded until they are set.
+#[derive(Clone, Copy, Decode, Default, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct RelayCallIndices {
+    /// Index of the `Balances` pallet.
+    pub balances: u8,
+    /// Index of `Balances::transfer`.
+    pub balances_transfer: u8,
+    /// Index of `Balances::transfer_keep_alive`.
+    pub balances_transfer_keep_alive: u8,
+    /// Index of `Balances::transfer_all`, if the Relay Chain has it.
+    pub balances_transfer_all: Option<u8>,
+    /// Index of the `Parachains` pallet.
+    pub parachains: u8,
+    /// Index of `Parachains::send_xcmp_message`.
+    pub parachains_send_xcmp_message: u8,
+}
+
+/// An upward message holding a hand-encoded Relay Chain call.
+///
+/// Unlike [`RococoUpwardMessage`] this does not link any Relay Chain runtime, the calls are
+/// encoded from the [`RelayCallIndices`] kept in the pallet storage, and cannot be built before
+/// they are set. The `dest` of balance
+/// transfers is encoded as a plain `AccountId`, so the Relay Chain must use it as lookup source.
+pub struct EncodedRelayCall<T>(Vec<u8>, PhantomData<T>);
+
+impl<T> EncodedRelayCall<T> {
+    fn new(call: impl Encode) -> Self {
+        EncodedRelayCall(call.encode(), PhantomData)
+    }
+
+    /// The encoded Relay Chain call.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Encodes as the bare call, so the Relay Chain can decode it as its own `Call`.
+impl<T> Encode for EncodedRelayCall<T> {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        dest.write(&self.0)
+    }
+}
+
+impl<T> codec::EncodeLike for EncodedRelayCall<T> {}
+
+impl<T> Decode for EncodedRelayCall<T> {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let len = input
+            .remaining_len()?
+            .ok_or("Length of the encoded relay call is unknown")?;
+        let mut call = Vec::new();
+        call.resize(len, 0);
+        input.read(&mut call)?;
+        Ok(EncodedRelayCall(call, PhantomData))
+    }
+}
+
+impl<T: Trait> BalancesMessage<T::AccountId, BalanceOf<T>> for EncodedRelayCall<T> {
+    fn transfer(dest: T::AccountId, amount: BalanceOf<T>) -> Option<Self> {
+        let indices = <Module<T>>::relay_call_indices()?;
+        let amount: Balance = convert_hack(&amount);
+        Some(Self::new((
+            indices.balances,
+            indices.balances_transfer,
+            dest,
+            Compact(amount),
+        )))
+    }
+
+    fn transfer_keep_alive(dest: T::AccountId, amount: BalanceOf<T>) -> Option<Self> {
+        let indices = <Module<T>>::relay_call_indices()?;
+        let amount: Balance = convert_hack(&amount);
+        Some(Self::new((
+            indices.balances,
+            indices.balances_transfer_keep_alive,
+            dest,
+            Compact(amount),
+        )))
+    }
+
+    fn transfer_all(dest: T::AccountId, keep_alive: bool) -> Option<Self> {
+        let indices = <Module<T>>::relay_call_indices()?;
+        let call_index = indices.balances_transfer_all?;
+        Some(Self::new((indices.balances, call_index, dest, keep_alive)))
+    }
+}
+
+impl<T: Trait> XCMPMessage for EncodedRelayCall<T> {
+    fn send_message(dest: ParaId, msg: Vec<u8>) -> Option<Self> {
+        let indices = <Module<T>>::relay_call_indices()?;
+        Some(Self::new((
+            indices.parachains,
+            indices.parachains_send_xcmp_message,
+            dest,
+            msg,
+        )))
     }
 }
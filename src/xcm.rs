@@ -0,0 +1,162 @@
+//! XCM v0 types used by this pallet to talk to other parachains.
+//!
+//! The `polkadot` branch this pallet builds against predates the `xcm` crate, so the subset of
+//! XCM v0 needed for fungible transfers is mirrored here. Variant indices are pinned to the ones
+//! of XCM v0 to keep the SCALE encoding, and so the messages, compatible with it.
+
+use codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// A global identifier of an account-bearing consensus system.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum NetworkId {
+    /// Unidentified/any.
+    Any,
+    /// Some named network.
+    Named(Vec<u8>),
+    /// The Polkadot Relay Chain.
+    Polkadot,
+    /// Kusama.
+    Kusama,
+}
+
+/// A single item in a path to describe the relative location of a consensus system.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Junction {
+    /// The consensus system of which the context is a member, e.g. the Relay Chain for a
+    /// parachain.
+    Parent,
+    /// An indexed parachain belonging to and operated by the context.
+    Parachain {
+        #[codec(compact)]
+        id: u32,
+    },
+    /// A 32-byte identifier for an account of a specific network.
+    AccountId32 { network: NetworkId, id: [u8; 32] },
+    /// An 8-byte index for an account of a specific network.
+    AccountIndex64 {
+        network: NetworkId,
+        #[codec(compact)]
+        index: u64,
+    },
+    /// A 20-byte identifier for an account of a specific network.
+    AccountKey20 { network: NetworkId, key: [u8; 20] },
+    /// An instanced, indexed pallet that forms a constituent part of the context.
+    PalletInstance { id: u8 },
+    /// A non-descript index within the context location.
+    GeneralIndex {
+        #[codec(compact)]
+        id: u128,
+    },
+    /// A nondescript datum acting as a key within the context location.
+    GeneralKey(Vec<u8>),
+    /// The unambiguous child.
+    OnlyChild,
+}
+
+/// A relative path between state-bearing consensus systems.
+///
+/// XCM v0 allows up to eight junctions, locations longer than four are not used by this pallet
+/// and fail to decode.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum MultiLocation {
+    /// The interpreting consensus system.
+    Null,
+    /// A relative path comprising one junction.
+    X1(Junction),
+    /// A relative path comprising two junctions.
+    X2(Junction, Junction),
+    /// A relative path comprising three junctions.
+    X3(Junction, Junction, Junction),
+    /// A relative path comprising four junctions.
+    X4(Junction, Junction, Junction, Junction),
+}
+
+/// A single general identifier for an asset, or a wildcard over assets.
+///
+/// Only the fungible variants are mirrored, non-fungible assets fail to decode.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum MultiAsset {
+    /// No assets.
+    #[codec(index = "0")]
+    None,
+    /// All assets.
+    #[codec(index = "1")]
+    All,
+    /// All fungible assets.
+    #[codec(index = "2")]
+    AllFungible,
+    /// All fungible assets of the given abstract `id`.
+    #[codec(index = "4")]
+    AllAbstractFungible { id: Vec<u8> },
+    /// All fungible assets of the given concrete `id`.
+    #[codec(index = "6")]
+    AllConcreteFungible { id: MultiLocation },
+    /// `amount` of the fungible asset identified by an abstract `id`.
+    #[codec(index = "8")]
+    AbstractFungible {
+        id: Vec<u8>,
+        #[codec(compact)]
+        amount: u128,
+    },
+    /// `amount` of the fungible asset identified by its concrete location `id`.
+    #[codec(index = "10")]
+    ConcreteFungible {
+        id: MultiLocation,
+        #[codec(compact)]
+        amount: u128,
+    },
+}
+
+/// An instruction to be executed on the holding account of an `Xcm`.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum Order {
+    /// Do nothing.
+    #[codec(index = "0")]
+    Null,
+    /// Remove the `assets` from holding and place them into `dest`.
+    #[codec(index = "1")]
+    DepositAsset {
+        assets: Vec<MultiAsset>,
+        dest: MultiLocation,
+    },
+}
+
+/// Cross-Consensus Message.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum Xcm {
+    /// Withdraw `assets` from the sender's account on the recipient into holding and execute
+    /// `effects` on them.
+    #[codec(index = "0")]
+    WithdrawAsset {
+        assets: Vec<MultiAsset>,
+        effects: Vec<Order>,
+    },
+    /// The sender, being the reserve of `assets`, has received them into the recipient's
+    /// account; place their equivalent in holding and execute `effects` on them.
+    #[codec(index = "1")]
+    ReserveAssetDeposit {
+        assets: Vec<MultiAsset>,
+        effects: Vec<Order>,
+    },
+    /// `assets` have been removed from the sender; mint them into holding and execute
+    /// `effects` on them.
+    #[codec(index = "2")]
+    TeleportAsset {
+        assets: Vec<MultiAsset>,
+        effects: Vec<Order>,
+    },
+}
+
+/// A versioned `Xcm`.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum VersionedXcm {
+    /// XCM v0.
+    V0(Xcm),
+}